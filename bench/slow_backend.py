@@ -0,0 +1,141 @@
+#!/usr/bin/env python3
+"""Measure throughput of cached reads while the database is stalled.
+
+The script starts lrbooks on SQLite with the in-memory cache, warms the
+cached book list, then holds an exclusive lock on the SQLite file. Each
+search uses a keyword nobody asked for before, so it misses the cache and
+waits on the lock until SQLite's busy timeout. While more of them are stuck
+than there are workers, the script counts how many cached `GET /books`
+requests complete. With blocking handlers the cached reads stall together
+with the searches (0 req/s); with handlers that move database calls off the
+async workers they keep flowing (1000 req/s or more on a laptop).
+
+The script exits with status 1 when the cached reads fall below
+`--min-rate` requests per second.
+
+Usage: bench/slow_backend.py [--min-rate N] [path/to/lrbooks]
+"""
+
+import argparse
+
+import os
+import re
+import sqlite3
+import subprocess
+import sys
+import tempfile
+import threading
+import time
+import urllib.error
+import urllib.request
+
+PORT = 18000
+WORKERS = 2
+SLOW_REQUESTS = 8  # more than WORKERS, so blocking handlers would run out
+FAST_CLIENTS = 4
+DURATION = 3.0
+MIN_RATE = 100.0
+
+ROOT = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
+
+
+def write_config(workdir):
+    with open(os.path.join(ROOT, "config.toml")) as f:
+        config = f.read()
+    config = re.sub(r"(?m)^port = .*$", f"port = {PORT}", config)
+    config = re.sub(r"(?m)^file_name = .*$", 'file_name = "bench.db"', config)
+    config = re.sub(r"(?m)^(book|review) = .*$", r'\1 = "sqlite"', config)
+    config = re.sub(r"(?m)^user = .*$", 'user = "memory"', config)
+    config = re.sub(r"(?m)^cache = .*$", 'cache = "memory"', config)
+    config = re.sub(r"(?m)^warm_up_pages = .*$", "warm_up_pages = 0", config)
+    with open(os.path.join(workdir, "config.toml"), "w") as f:
+        f.write(config)
+
+
+def get(path, timeout=30):
+    url = f"http://127.0.0.1:{PORT}{path}"
+    try:
+        with urllib.request.urlopen(url, timeout=timeout) as resp:
+            return resp.status
+    except urllib.error.HTTPError as e:
+        return e.code
+
+
+def wait_ready(proc):
+    for _ in range(100):
+        if proc.poll() is not None:
+            sys.exit("lrbooks exited during startup")
+        try:
+            get("/", timeout=1)
+            return
+        except OSError:
+            time.sleep(0.1)
+    sys.exit("lrbooks did not start")
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Measure cached reads while the database is stalled.")
+    parser.add_argument("binary", nargs="?", default=os.path.join(ROOT, "target/release/lrbooks"))
+    parser.add_argument(
+        "--min-rate", type=float, default=MIN_RATE,
+        help=f"fail below this many cached reads per second (default {MIN_RATE:.0f})",
+    )
+    args = parser.parse_args()
+    binary = os.path.abspath(args.binary)
+    with tempfile.TemporaryDirectory() as workdir:
+        write_config(workdir)
+        env = dict(os.environ, ROCKET_WORKERS=str(WORKERS), ROCKET_LOG_LEVEL="off")
+        proc = subprocess.Popen(
+            [binary], cwd=workdir, env=env,
+            stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL,
+        )
+        try:
+            wait_ready(proc)
+            get("/books")  # fill the cache
+
+            lock = sqlite3.connect(os.path.join(workdir, "bench.db"), isolation_level=None)
+            lock.execute("BEGIN EXCLUSIVE")
+            slow = [
+                threading.Thread(target=get, args=(f"/books?q=slow{i}",))
+                for i in range(SLOW_REQUESTS)
+            ]
+            for t in slow:
+                t.start()
+            time.sleep(0.2)  # let the searches reach the database
+
+            done = [0] * FAST_CLIENTS
+            deadline = time.monotonic() + DURATION
+
+            def client(n):
+                while time.monotonic() < deadline:
+                    try:
+                        if get("/books", timeout=DURATION) == 200:
+                            done[n] += 1
+                    except OSError:
+                        pass
+
+            fast = [threading.Thread(target=client, args=(n,)) for n in range(FAST_CLIENTS)]
+            for t in fast:
+                t.start()
+            for t in fast:
+                t.join()
+            lock.execute("ROLLBACK")
+            for t in slow:
+                t.join()
+
+            total = sum(done)
+            rate = total / DURATION
+            print(
+                f"{SLOW_REQUESTS} stalled searches on {WORKERS} workers: "
+                f"{total} cached reads in {DURATION:.0f}s ({rate:.0f} req/s)"
+            )
+        finally:
+            proc.terminate()
+            proc.wait()
+    if rate < args.min_rate:
+        sys.exit(f"FAIL: expected at least {args.min_rate:.0f} req/s")
+    print(f"OK: at least {args.min_rate:.0f} req/s")
+
+
+if __name__ == "__main__":
+    main()
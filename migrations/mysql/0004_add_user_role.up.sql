@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'User';
+UPDATE users SET role = 'Admin' WHERE is_admin;
+ALTER TABLE users DROP COLUMN is_admin
@@ -0,0 +1 @@
+ALTER TABLE reviews ADD COLUMN user_id INT NOT NULL DEFAULT 0
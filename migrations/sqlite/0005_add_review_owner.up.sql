@@ -0,0 +1 @@
+ALTER TABLE reviews ADD COLUMN user_id INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,47 @@
+use std::error::Error;
+
+use crate::application::WireHelper;
+use crate::infrastructure::database::migration;
+use crate::infrastructure::Config;
+
+const MIGRATE_USAGE: &str = "usage: lrbooks migrate [up | down [steps] | status]";
+
+// migrate runs `lrbooks migrate` against every configured SQL database.
+pub fn migrate(c: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let cmd = args.first().map(String::as_str).unwrap_or("up");
+    let steps: usize = match (cmd, args.get(1)) {
+        ("down", Some(n)) => n.parse().map_err(|_| MIGRATE_USAGE)?,
+        ("down", None) => 1,
+        ("up" | "status", None) => 0,
+        _ => return Err(MIGRATE_USAGE.into()),
+    };
+    let migrators = WireHelper::migrators(c)?;
+    if migrators.is_empty() {
+        println!("No SQL database is configured, nothing to migrate");
+    }
+    for (db, m) in migrators {
+        match cmd {
+            "up" => {
+                let done = migration::migrate_up(m.as_ref())?;
+                for mig in &done {
+                    println!("{db}: applied {}", mig.name);
+                }
+                if done.is_empty() {
+                    println!("{db}: up to date");
+                }
+            }
+            "down" => {
+                for mig in migration::migrate_down(m.as_ref(), steps)? {
+                    println!("{db}: reverted {}", mig.name);
+                }
+            }
+            _ => {
+                for (mig, applied) in migration::status(m.as_ref())? {
+                    let state = if applied { "applied" } else { "pending" };
+                    println!("{db}: {} {state}", mig.name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
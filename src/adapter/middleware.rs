@@ -1,37 +1,134 @@
+use std::ops::Deref;
+
 use rocket::http::Status;
 use rocket::request::{self, FromRequest, Request};
 
-use crate::domain::model::UserPermission;
+use crate::adapter::router::blocking;
+use crate::domain::model::{AuthUser, UserPermission};
+use crate::domain::DomainError;
 use crate::RestHandler;
 
-// Define a struct to hold the permission level required for the route
-pub struct PermCheck;
+// Realm named in every `WWW-Authenticate` challenge.
+pub const REALM: &str = "lrbooks";
+
+// AuthFailure is why a guard turned a request away, kept on the request for
+// the catcher to report.
+pub struct AuthFailure {
+    pub error: DomainError,
+    // Value of the `WWW-Authenticate` header.
+    pub challenge: Option<String>,
+}
+
+// permission_guard defines a guard admitting callers with at least `$perm`
+// and handing them to the route as an AuthUser.
+macro_rules! permission_guard {
+    ($name:ident, $perm:expr) => {
+        pub struct $name(pub AuthUser);
+
+        #[rocket::async_trait]
+        impl<'r> FromRequest<'r> for $name {
+            type Error = DomainError;
+
+            async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+                require(request, $perm).map($name)
+            }
+        }
+
+        impl Deref for $name {
+            type Target = AuthUser;
+
+            fn deref(&self) -> &AuthUser {
+                &self.0
+            }
+        }
+    };
+}
+
+// RequireUser admits any signed-in user.
+permission_guard!(RequireUser, UserPermission::User);
+// RequireAuthor admits users who may edit books.
+permission_guard!(RequireAuthor, UserPermission::Author);
+// RequireAdmin admits admins only.
+permission_guard!(RequireAdmin, UserPermission::Admin);
+
+// BearerToken is the raw token a request was sent with, left for the route
+// to check.
+pub struct BearerToken(pub String);
 
-// Implement FromRequest trait to perform permission check
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for PermCheck {
-    type Error = &'static str;
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = DomainError;
 
     async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        let auth_header = match request.headers().get_one("Authorization") {
-            Some(header) => header,
-            None => return request::Outcome::Error((Status::Unauthorized, "Token is required")),
-        };
-        let token = auth_header.trim_start_matches("Bearer ");
-        let rest_handler = request.rocket().state::<RestHandler>().unwrap();
-        // Check user permission against required permission
-        match rest_handler
-            .user_operator
-            .has_permission(token, UserPermission::Author)
-        {
-            Ok(b) => {
-                if b {
-                    request::Outcome::Success(PermCheck {})
-                } else {
-                    request::Outcome::Error((Status::Unauthorized, "Unauthorized"))
-                }
-            }
-            Err(_) => request::Outcome::Error((Status::BadRequest, "Invalid token")),
+        match bearer_token(request) {
+            Some(token) => request::Outcome::Success(BearerToken(token.to_string())),
+            None => reject(
+                request,
+                Status::Unauthorized,
+                DomainError::Unauthorized("Token is required".to_string()),
+                Some(format!("Bearer realm=\"{REALM}\"")),
+            ),
         }
     }
 }
+
+fn bearer_token<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request
+        .headers()
+        .get_one("Authorization")
+        .map(|header| header.trim_start_matches("Bearer "))
+}
+
+// require admits requests with a valid access token granting at least
+// `perm`.
+fn require(request: &Request<'_>, perm: UserPermission) -> request::Outcome<AuthUser, DomainError> {
+    let Some(token) = bearer_token(request) else {
+        return reject(
+            request,
+            Status::Unauthorized,
+            DomainError::Unauthorized("Token is required".to_string()),
+            Some(format!("Bearer realm=\"{REALM}\"")),
+        );
+    };
+    let rest_handler = request.rocket().state::<RestHandler>().unwrap();
+    // Revocations are looked up in the cache.
+    match blocking(|| rest_handler.user_operator.authenticate(token)) {
+        Ok(user) if user.permission >= perm => request::Outcome::Success(user),
+        Ok(_) => reject(
+            request,
+            Status::Forbidden,
+            DomainError::Forbidden("Insufficient permission".to_string()),
+            Some(format!(
+                "Bearer realm=\"{REALM}\", error=\"insufficient_scope\""
+            )),
+        ),
+        Err(err @ DomainError::Unauthorized(_)) => {
+            let challenge = format!(
+                "Bearer realm=\"{REALM}\", error=\"invalid_token\", error_description=\"{}\"",
+                quote_safe(&err.to_string())
+            );
+            reject(request, Status::Unauthorized, err, Some(challenge))
+        }
+        Err(err) => reject(request, Status::ServiceUnavailable, err, None),
+    }
+}
+
+fn reject<T>(
+    request: &Request<'_>,
+    status: Status,
+    error: DomainError,
+    challenge: Option<String>,
+) -> request::Outcome<T, DomainError> {
+    request.local_cache(|| {
+        Some(AuthFailure {
+            error: error.clone(),
+            challenge,
+        })
+    });
+    request::Outcome::Error((status, error))
+}
+
+// quote_safe drops the characters that would end a quoted header value.
+fn quote_safe(s: &str) -> String {
+    s.chars().filter(|c| *c != '"' && *c != '\\').collect()
+}
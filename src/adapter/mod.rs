@@ -1,4 +1,5 @@
 pub mod router;
 pub use router::make_router;
 
+pub mod cli;
 pub mod middleware;
@@ -1,22 +1,129 @@
 use rocket::http::Status;
-use rocket::response::{content, status};
+use rocket::request::Request;
+use rocket::response::{self, content, status, Responder};
 use rocket::serde::json::Json;
 
-use crate::adapter::middleware::PermCheck;
+use crate::adapter::middleware::{
+    AuthFailure, BearerToken, RequireAdmin, RequireAuthor, RequireUser, REALM,
+};
 use crate::application;
 use crate::application::dto;
 use crate::application::executor;
 use crate::domain::model;
+use crate::domain::{DomainError, FieldError};
+use crate::infrastructure::cache;
 
 pub struct RestHandler {
     book_operator: executor::BookOperator,
     review_operator: executor::ReviewOperator,
     pub user_operator: executor::UserOperator,
+    cache_operator: executor::CacheOperator,
+    health_operator: executor::HealthOperator,
+}
+
+impl RestHandler {
+    // warm_up_cache loads the first `pages` pages of the book list.
+    pub fn warm_up_cache(&self, pages: u32, page_size: u32) -> Result<u32, DomainError> {
+        self.book_operator.warm_up(pages, page_size)
+    }
 }
 
 #[derive(serde::Serialize)]
 pub struct ErrorResponse {
+    code: &'static str,
     error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError>,
+}
+
+// ErrorReply is an error response; a 401 also challenges the client to
+// authenticate.
+pub struct ErrorReply {
+    status: Status,
+    body: ErrorResponse,
+    challenge: Option<String>,
+}
+
+impl<'r> Responder<'r, 'static> for ErrorReply {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = status::Custom(self.status, Json(self.body)).respond_to(req)?;
+        if let Some(challenge) = self.challenge {
+            response.set_raw_header("WWW-Authenticate", challenge);
+        }
+        Ok(response)
+    }
+}
+
+// Map every domain error to its HTTP status in one place.
+impl From<DomainError> for ErrorReply {
+    fn from(err: DomainError) -> Self {
+        let status = match err {
+            DomainError::NotFound(_) => Status::NotFound,
+            DomainError::Validation(_) | DomainError::InvalidFields(_) => Status::BadRequest,
+            DomainError::Conflict(_) => Status::Conflict,
+            DomainError::Unauthorized(_) => Status::Unauthorized,
+            DomainError::Forbidden(_) => Status::Forbidden,
+            DomainError::Backend(_) => Status::ServiceUnavailable,
+        };
+        let code = err.code();
+        let error = err.to_string();
+        let fields = match err {
+            DomainError::InvalidFields(fields) => fields,
+            _ => Vec::new(),
+        };
+        ErrorReply {
+            status,
+            body: ErrorResponse {
+                code,
+                error,
+                fields,
+            },
+            challenge: basic_challenge(status),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for DomainError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        ErrorReply::from(self).respond_to(req)
+    }
+}
+
+// Catch errors raised before a handler runs, such as failed guards or
+// malformed bodies, so they share the ErrorResponse shape.
+#[catch(default)]
+pub fn default_catcher(status: Status, req: &Request) -> ErrorReply {
+    // A failed permission guard left its error and challenge behind.
+    if let Some(failure) = req.local_cache(|| None::<AuthFailure>) {
+        return ErrorReply {
+            status,
+            challenge: failure.challenge.clone(),
+            ..failure.error.clone().into()
+        };
+    }
+    let code = match status.code {
+        400 | 422 => "validation_failed",
+        401 => "unauthorized",
+        403 => "forbidden",
+        404 => "not_found",
+        409 => "conflict",
+        503 => "backend_unavailable",
+        _ => "internal_error",
+    };
+    let error = status.reason().unwrap_or("Unknown error").to_string();
+    ErrorReply {
+        status,
+        body: ErrorResponse {
+            code,
+            error,
+            fields: Vec::new(),
+        },
+        challenge: basic_challenge(status),
+    }
+}
+
+fn basic_challenge(status: Status) -> Option<String> {
+    (status == Status::Unauthorized).then(|| format!("Bearer realm=\"{REALM}\""))
 }
 
 // Define a health endpoint handler, use `/health` or `/`
@@ -26,234 +133,299 @@ pub fn health_check() -> content::RawJson<&'static str> {
     content::RawJson("{\"status\":\"ok\"}")
 }
 
+// Liveness only tells the process is serving requests.
+#[get("/health/live")]
+pub fn liveness() -> content::RawJson<&'static str> {
+    content::RawJson("{\"status\":\"ok\"}")
+}
+
+// Readiness fails with 503 while a required backend is down.
+#[get("/health/ready")]
+pub async fn readiness(
+    rest_handler: &rocket::State<RestHandler>,
+) -> status::Custom<Json<dto::Readiness>> {
+    let r = blocking(|| rest_handler.health_operator.readiness());
+    let status = if r.status == "unavailable" {
+        Status::ServiceUnavailable
+    } else {
+        Status::Ok
+    };
+    status::Custom(status, Json(r))
+}
+
 #[get("/books?<o>&<q>")]
-pub fn get_books(
+pub async fn get_books(
     rest_handler: &rocket::State<RestHandler>,
     o: Option<u32>,
     q: Option<&str>,
-) -> Result<Json<Vec<model::Book>>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler
-        .book_operator
-        .get_books(o.unwrap_or(0), q.unwrap_or(""))
-    {
-        Ok(books) => Ok(Json(books)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<Vec<model::Book>>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .book_operator
+            .get_books(o.unwrap_or(0), q.unwrap_or(""))
+            .map(Json)
+    })
 }
 
 #[get("/books/<id>")]
-pub fn get_book(
+pub async fn get_book(
     rest_handler: &rocket::State<RestHandler>,
     id: u32,
-) -> Result<Json<model::Book>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.book_operator.get_book(id) {
-        Ok(book) => match book {
-            Some(b) => Ok(Json(b)),
-            None => Err(status::Custom(
-                Status::NotFound,
-                Json(ErrorResponse {
-                    error: format!("book {id} not found"),
-                }),
-            )),
-        },
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<model::Book>, DomainError> {
+    blocking(|| match rest_handler.book_operator.get_book(id)? {
+        Some(b) => Ok(Json(b)),
+        None => Err(DomainError::NotFound(format!("book {id} not found"))),
+    })
 }
 
 #[post("/books", format = "json", data = "<book>")]
-pub fn create_book(
+pub async fn create_book(
     rest_handler: &rocket::State<RestHandler>,
-    book: Json<model::Book>,
-    _perm_check: PermCheck,
-) -> Result<Json<model::Book>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.book_operator.create_book(book.into_inner()) {
-        Ok(b) => Ok(Json(b)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+    book: Json<dto::BookCreateBody>,
+    _author: RequireAuthor,
+) -> Result<Json<model::Book>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .book_operator
+            .create_book(&book.into_inner())
+            .map(Json)
+    })
 }
 
 #[put("/books/<id>", format = "json", data = "<book>")]
-pub fn update_book(
+pub async fn update_book(
     rest_handler: &rocket::State<RestHandler>,
     id: u32,
-    book: Json<model::Book>,
-    _perm_check: PermCheck,
-) -> Result<Json<model::Book>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler
-        .book_operator
-        .update_book(id, book.into_inner())
-    {
-        Ok(b) => Ok(Json(b)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+    book: Json<dto::BookUpdateBody>,
+    _author: RequireAuthor,
+) -> Result<Json<model::Book>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .book_operator
+            .update_book(id, &book.into_inner())
+            .map(Json)
+    })
+}
+
+#[patch("/books/<id>", data = "<patch>")]
+pub async fn patch_book(
+    rest_handler: &rocket::State<RestHandler>,
+    id: u32,
+    patch: Json<dto::BookPatchBody>,
+    _author: RequireAuthor,
+) -> Result<Json<model::Book>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .book_operator
+            .patch_book(id, &patch.into_inner())
+            .map(Json)
+    })
 }
 
 #[delete("/books/<id>")]
-pub fn delete_book(
+pub async fn delete_book(
     rest_handler: &rocket::State<RestHandler>,
     id: u32,
-    _perm_check: PermCheck,
-) -> Result<status::NoContent, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.book_operator.delete_book(id) {
-        Ok(_) => Ok(status::NoContent),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+    _author: RequireAuthor,
+) -> Result<status::NoContent, DomainError> {
+    blocking(|| {
+        rest_handler
+            .book_operator
+            .delete_book(id)
+            .map(|_| status::NoContent)
+    })
 }
 
 #[get("/books/<id>/reviews?<q>")]
-pub fn get_reviews_of_book(
+pub async fn get_reviews_of_book(
     rest_handler: &rocket::State<RestHandler>,
     id: u32,
     q: Option<&str>,
-) -> Result<Json<Vec<model::Review>>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler
-        .review_operator
-        .get_reviews_of_book(id, q.unwrap_or(""))
-    {
-        Ok(reviews) => Ok(Json(reviews)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<Vec<model::Review>>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .review_operator
+            .get_reviews_of_book(id, q.unwrap_or(""))
+            .map(Json)
+    })
 }
 
 #[get("/reviews/<id>")]
-pub fn get_review(
+pub async fn get_review(
     rest_handler: &rocket::State<RestHandler>,
     id: &str,
-) -> Result<Json<model::Review>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.review_operator.get_review(id) {
-        Ok(review) => match review {
-            Some(r) => Ok(Json(r)),
-            None => Err(status::Custom(
-                Status::NotFound,
-                Json(ErrorResponse {
-                    error: format!("review {id} not found"),
-                }),
-            )),
-        },
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<model::Review>, DomainError> {
+    blocking(|| match rest_handler.review_operator.get_review(id)? {
+        Some(r) => Ok(Json(r)),
+        None => Err(DomainError::NotFound(format!("review {id} not found"))),
+    })
 }
 
 #[post("/reviews", format = "json", data = "<review>")]
-pub fn create_review(
+pub async fn create_review(
     rest_handler: &rocket::State<RestHandler>,
     review: Json<dto::ReviewBody>,
-) -> Result<Json<model::Review>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler
-        .review_operator
-        .create_review(&review.into_inner())
-    {
-        Ok(b) => Ok(Json(b)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+    user: RequireUser,
+) -> Result<Json<model::Review>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .review_operator
+            .create_review(&review.into_inner(), &user)
+            .map(Json)
+    })
 }
 
 #[put("/reviews/<id>", format = "json", data = "<review>")]
-pub fn update_review(
+pub async fn update_review(
     rest_handler: &rocket::State<RestHandler>,
     id: &str,
     review: Json<dto::ReviewBody>,
-) -> Result<Json<model::Review>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler
-        .review_operator
-        .update_review(id, review.into_inner())
-    {
-        Ok(b) => Ok(Json(b)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+    user: RequireUser,
+) -> Result<Json<model::Review>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .review_operator
+            .update_review(id, review.into_inner(), &user)
+            .map(Json)
+    })
+}
+
+#[patch("/reviews/<id>", data = "<patch>")]
+pub async fn patch_review(
+    rest_handler: &rocket::State<RestHandler>,
+    id: &str,
+    patch: Json<dto::ReviewPatchBody>,
+    user: RequireUser,
+) -> Result<Json<model::Review>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .review_operator
+            .patch_review(id, &patch.into_inner(), &user)
+            .map(Json)
+    })
 }
 
 #[delete("/reviews/<id>")]
-pub fn delete_review(
+pub async fn delete_review(
     rest_handler: &rocket::State<RestHandler>,
     id: &str,
-) -> Result<status::NoContent, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.review_operator.delete_review(id) {
-        Ok(_) => Ok(status::NoContent),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+    user: RequireUser,
+) -> Result<status::NoContent, DomainError> {
+    blocking(|| {
+        rest_handler
+            .review_operator
+            .delete_review(id, &user)
+            .map(|_| status::NoContent)
+    })
 }
 
 #[post("/users", format = "json", data = "<uc>")]
-pub fn user_sign_up(
+pub async fn user_sign_up(
     rest_handler: &rocket::State<RestHandler>,
     uc: Json<dto::UserCredential>,
-) -> Result<Json<dto::User>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.user_operator.create_user(&uc.into_inner()) {
-        Ok(u) => Ok(Json(u)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<dto::User>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .user_operator
+            .create_user(&uc.into_inner())
+            .map(Json)
+    })
 }
 
 #[post("/users/sign-in", format = "json", data = "<uc>")]
-pub fn user_sign_in(
+pub async fn user_sign_in(
     rest_handler: &rocket::State<RestHandler>,
     uc: Json<dto::UserCredential>,
-) -> Result<Json<dto::UserToken>, status::Custom<Json<ErrorResponse>>> {
-    match rest_handler.user_operator.sign_in(&uc.email, &uc.password) {
-        Ok(u) => Ok(Json(u)),
-        Err(err) => Err(status::Custom(
-            Status::InternalServerError,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<dto::UserToken>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .user_operator
+            .sign_in(&uc.email, &uc.password)
+            .map(Json)
+    })
+}
+
+#[post("/users/refresh", format = "json", data = "<body>")]
+pub async fn user_refresh(
+    rest_handler: &rocket::State<RestHandler>,
+    body: Json<dto::RefreshRequest>,
+) -> Result<Json<dto::Tokens>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .user_operator
+            .refresh(&body.refresh_token)
+            .map(Json)
+    })
+}
+
+#[get("/users/me")]
+pub async fn get_me(user: RequireUser) -> Json<model::AuthUser> {
+    Json(user.0)
+}
+
+#[post("/users/sign-out")]
+pub async fn user_sign_out(
+    rest_handler: &rocket::State<RestHandler>,
+    token: BearerToken,
+) -> Result<status::NoContent, DomainError> {
+    blocking(|| {
+        rest_handler
+            .user_operator
+            .sign_out(&token.0)
+            .map(|_| status::NoContent)
+    })
+}
+
+#[get("/admin/users?<o>")]
+pub async fn get_users(
+    rest_handler: &rocket::State<RestHandler>,
+    o: Option<u32>,
+    _admin: RequireAdmin,
+) -> Result<Json<Vec<dto::User>>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .user_operator
+            .get_users(o.unwrap_or(0))
+            .map(Json)
+    })
+}
+
+#[put("/admin/users/<id>/role", format = "json", data = "<body>")]
+pub async fn update_user_role(
+    rest_handler: &rocket::State<RestHandler>,
+    id: u32,
+    body: Json<dto::RoleBody>,
+    _admin: RequireAdmin,
+) -> Result<Json<dto::User>, DomainError> {
+    blocking(|| {
+        rest_handler
+            .user_operator
+            .update_role(id, &body.into_inner())
+            .map(Json)
+    })
+}
+
+#[post("/admin/cache/flush")]
+pub async fn flush_cache(
+    rest_handler: &rocket::State<RestHandler>,
+    _admin: RequireAdmin,
+) -> Result<Json<dto::CacheFlushed>, DomainError> {
+    blocking(|| rest_handler.cache_operator.flush().map(Json))
+}
+
+#[get("/admin/cache/stats")]
+pub async fn get_cache_stats(
+    rest_handler: &rocket::State<RestHandler>,
+    _admin: RequireAdmin,
+) -> Json<Vec<cache::TierStats>> {
+    blocking(|| Json(rest_handler.cache_operator.stats()))
+}
+
+// blocking runs a call into the operators, whose drivers are synchronous, so
+// a slow database or cache holds up only this request instead of an async
+// worker shared with other requests.
+pub(crate) fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    rocket::tokio::task::block_in_place(f)
 }
 
 pub fn make_router(wire_helper: &application::WireHelper) -> RestHandler {
@@ -261,11 +433,26 @@ pub fn make_router(wire_helper: &application::WireHelper) -> RestHandler {
         book_operator: executor::BookOperator::new(
             wire_helper.book_manager(),
             wire_helper.cache_helper(),
+            wire_helper.cache_policy(),
+        ),
+        review_operator: executor::ReviewOperator::new(
+            wire_helper.review_manager(),
+            wire_helper.user_manager(),
+            wire_helper.cache_helper(),
+            wire_helper.cache_policy(),
         ),
-        review_operator: executor::ReviewOperator::new(wire_helper.review_manager()),
         user_operator: executor::UserOperator::new(
             wire_helper.user_manager(),
             wire_helper.perm_manager(),
+            wire_helper.password_manager(),
+        ),
+        cache_operator: executor::CacheOperator::new(
+            wire_helper.cache_helper(),
+            wire_helper.cache_policy(),
+        ),
+        health_operator: executor::HealthOperator::new(
+            wire_helper.dependencies(),
+            wire_helper.health_timeout(),
         ),
     }
 }
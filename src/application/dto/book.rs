@@ -0,0 +1,32 @@
+use super::merge_patch::deserialize_some;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BookCreateBody {
+    pub title: String,
+    pub author: String,
+    pub published_at: String,
+    #[serde(default)]
+    pub description: String,
+    pub isbn: String,
+    pub total_pages: u32,
+}
+
+// A PUT replaces every writable field, so it takes the same shape.
+pub type BookUpdateBody = BookCreateBody;
+
+// BookPatchBody is a JSON Merge Patch document for a book.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct BookPatchBody {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub author: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub published_at: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub isbn: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub total_pages: Option<Option<u32>>,
+}
@@ -0,0 +1,4 @@
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CacheFlushed {
+    pub removed: u64,
+}
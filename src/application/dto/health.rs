@@ -0,0 +1,15 @@
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: String,
+    pub required: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Readiness {
+    pub status: String,
+    pub components: Vec<ComponentHealth>,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Deserializer};
+
+// deserialize_some keeps an explicit `null` apart from a missing member, as
+// JSON Merge Patch (RFC 7396) requires: a missing member deserializes to
+// `None` through `#[serde(default)]`, and `null` to `Some(None)`.
+pub fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
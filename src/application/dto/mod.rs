@@ -1,5 +1,16 @@
+mod book;
+pub use book::{BookCreateBody, BookPatchBody, BookUpdateBody};
+
+mod cache;
+pub use cache::CacheFlushed;
+
+mod health;
+pub use health::{ComponentHealth, Readiness};
+
+mod merge_patch;
+
 mod review;
-pub use review::ReviewBody;
+pub use review::{ReviewBody, ReviewPatchBody};
 
 mod user;
-pub use user::{User, UserCredential, UserToken};
+pub use user::{RefreshRequest, RoleBody, Tokens, User, UserCredential, UserToken};
@@ -1,7 +1,17 @@
+use super::merge_patch::deserialize_some;
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ReviewBody {
     pub book_id: u32,
-    pub author: String,
     pub title: String,
     pub content: String,
 }
+
+// ReviewPatchBody is a JSON Merge Patch document for a review.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ReviewPatchBody {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub content: Option<Option<String>>,
+}
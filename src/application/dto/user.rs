@@ -1,3 +1,5 @@
+use crate::domain::model;
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct UserCredential {
     pub email: String,
@@ -8,10 +10,50 @@ pub struct UserCredential {
 pub struct User {
     pub id: u32,
     pub email: String,
+    pub role: model::UserPermission,
+}
+
+impl From<model::User> for User {
+    fn from(u: model::User) -> Self {
+        User {
+            id: u.id,
+            email: u.email,
+            role: u.role,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RoleBody {
+    pub role: model::UserPermission,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct UserToken {
     pub user: User,
+    #[serde(flatten)]
+    pub tokens: Tokens,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Tokens {
     pub token: String,
+    pub refresh_token: String,
+    // Seconds until `token` expires.
+    pub expires_in: u64,
+}
+
+impl From<model::TokenPair> for Tokens {
+    fn from(p: model::TokenPair) -> Self {
+        Tokens {
+            token: p.access_token,
+            refresh_token: p.refresh_token,
+            expires_in: p.expires_in,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
 }
@@ -1,68 +1,399 @@
 use std::sync::Arc;
 
+use chrono::NaiveDate;
+
+use super::read_through::{query_key, ReadThrough};
+use crate::application::dto;
 use crate::domain::gateway;
 use crate::domain::model;
+use crate::domain::{DomainError, FieldError};
 use crate::infrastructure::cache;
 
-const BOOKS_KEY: &str = "lr-books";
+// Every book change bumps the version of this key space, so books and lists
+// cached under an older version are never read again and simply expire.
+pub(super) const BOOKS_KEY: &str = "books";
 
 pub struct BookOperator {
     book_manager: Arc<dyn gateway::BookManager>,
-    cache_helper: Arc<dyn cache::Helper>,
+    cache: ReadThrough,
+    policy: cache::Policy,
 }
 
 impl BookOperator {
-    pub fn new(b: Arc<dyn gateway::BookManager>, c: Arc<dyn cache::Helper>) -> Self {
+    pub fn new(
+        b: Arc<dyn gateway::BookManager>,
+        c: Arc<dyn cache::Helper>,
+        p: &cache::Policy,
+    ) -> Self {
         BookOperator {
             book_manager: b,
-            cache_helper: c,
+            cache: ReadThrough::new(c, p, BOOKS_KEY),
+            policy: p.clone(),
         }
     }
 
-    pub fn create_book(&self, b: model::Book) -> Result<model::Book, Box<dyn std::error::Error>> {
-        let id = self.book_manager.create_book(&b)?;
-        let mut book = b;
-        book.id = id;
-        Ok(book)
+    pub fn create_book(&self, body: &dto::BookCreateBody) -> Result<model::Book, DomainError> {
+        validate_book(body)?;
+        let id = self.book_manager.create_book(&to_model(body))?;
+        self.cache.invalidate();
+        self.book_manager
+            .get_book(id)?
+            .ok_or_else(|| book_not_found(id))
     }
 
-    pub fn get_book(&self, id: u32) -> Result<Option<model::Book>, Box<dyn std::error::Error>> {
-        self.book_manager.get_book(id)
+    pub fn get_book(&self, id: u32) -> Result<Option<model::Book>, DomainError> {
+        self.cache
+            .get(&format!("id-{id}"), self.policy.book_ttl, || {
+                self.book_manager.get_book(id)
+            })
     }
 
-    pub fn get_books(
-        &self,
-        offset: u32,
-        query: &str,
-    ) -> Result<Vec<model::Book>, Box<dyn std::error::Error>> {
-        // Search results, don't cache it
-        if !query.is_empty() {
-            return self.book_manager.get_books(offset, query);
-        }
-        // Normal list of results
-        let k = format!("{}-{}", BOOKS_KEY, offset);
-        let raw_value = self.cache_helper.load(&k)?;
-        if let Some(v) = raw_value {
-            let cached_books = serde_json::from_str(&v)?;
-            Ok(cached_books)
+    pub fn get_books(&self, offset: u32, query: &str) -> Result<Vec<model::Book>, DomainError> {
+        let (k, ttl) = if query.is_empty() {
+            (offset.to_string(), self.policy.book_list_ttl)
         } else {
-            let fetched_books = self.book_manager.get_books(offset, "")?;
-            let v = serde_json::to_string(&fetched_books)?;
-            self.cache_helper.save(&k, &v)?;
-            Ok(fetched_books)
+            (
+                format!("{offset}-q-{}", query_key(query)),
+                self.policy.search_ttl,
+            )
+        };
+        self.cache
+            .get(&k, ttl, || self.book_manager.get_books(offset, query))
+    }
+
+    // warm_up loads the first `pages` pages of the book list into the cache
+    // and returns how many pages it loaded.
+    pub fn warm_up(&self, pages: u32, page_size: u32) -> Result<u32, DomainError> {
+        for page in 0..pages {
+            let books = self.get_books(page * page_size, "")?;
+            if books.len() < page_size as usize {
+                return Ok(page + 1);
+            }
         }
+        Ok(pages)
     }
 
     pub fn update_book(
         &self,
         id: u32,
-        b: model::Book,
-    ) -> Result<model::Book, Box<dyn std::error::Error>> {
-        self.book_manager.update_book(id, &b)?;
-        Ok(b)
+        body: &dto::BookUpdateBody,
+    ) -> Result<model::Book, DomainError> {
+        validate_book(body)?;
+        if !self.book_manager.update_book(id, &to_model(body))? {
+            return Err(book_not_found(id));
+        }
+        self.cache.invalidate();
+        self.book_manager
+            .get_book(id)?
+            .ok_or_else(|| book_not_found(id))
+    }
+
+    pub fn patch_book(
+        &self,
+        id: u32,
+        body: &dto::BookPatchBody,
+    ) -> Result<model::Book, DomainError> {
+        let patch = to_patch(body)?;
+        if !self.book_manager.patch_book(id, &patch)? {
+            return Err(book_not_found(id));
+        }
+        self.cache.invalidate();
+        self.book_manager
+            .get_book(id)?
+            .ok_or_else(|| book_not_found(id))
+    }
+
+    pub fn delete_book(&self, id: u32) -> Result<(), DomainError> {
+        if !self.book_manager.delete_book(id)? {
+            return Err(book_not_found(id));
+        }
+        self.cache.invalidate();
+        Ok(())
+    }
+}
+
+fn book_not_found(id: u32) -> DomainError {
+    DomainError::NotFound(format!("book {id} not found"))
+}
+
+fn to_model(body: &dto::BookCreateBody) -> model::Book {
+    model::Book {
+        id: 0,
+        title: body.title.trim().to_string(),
+        author: body.author.trim().to_string(),
+        published_at: body.published_at.clone(),
+        description: body.description.clone(),
+        isbn: normalize_isbn(&body.isbn),
+        total_pages: body.total_pages,
+        created_at: String::new(),
+        updated_at: String::new(),
+    }
+}
+
+// to_patch turns a merge patch into a BookPatch. Only `description` may be
+// removed with `null`; the other fields are required.
+fn to_patch(body: &dto::BookPatchBody) -> Result<model::BookPatch, DomainError> {
+    let mut errs = Vec::new();
+    let patch = model::BookPatch {
+        title: required("title", &body.title, &mut errs).map(|v| v.trim().to_string()),
+        author: required("author", &body.author, &mut errs).map(|v| v.trim().to_string()),
+        published_at: required("published_at", &body.published_at, &mut errs),
+        description: body.description.clone().map(Option::unwrap_or_default),
+        isbn: required("isbn", &body.isbn, &mut errs).map(|v| normalize_isbn(&v)),
+        total_pages: required("total_pages", &body.total_pages, &mut errs),
+    };
+    check_fields(&patch, &mut errs);
+    if errs.is_empty() {
+        Ok(patch)
+    } else {
+        Err(DomainError::InvalidFields(errs))
+    }
+}
+
+fn required<T: Clone>(
+    field: &'static str,
+    value: &Option<Option<T>>,
+    errs: &mut Vec<FieldError>,
+) -> Option<T> {
+    match value {
+        Some(None) => {
+            errs.push(FieldError::new(field, "must not be null"));
+            None
+        }
+        Some(Some(v)) => Some(v.clone()),
+        None => None,
+    }
+}
+
+// validate_book checks every field and reports all failures together.
+fn validate_book(body: &dto::BookCreateBody) -> Result<(), DomainError> {
+    let mut errs = Vec::new();
+    let b = to_model(body);
+    check_fields(
+        &model::BookPatch {
+            title: Some(b.title),
+            author: Some(b.author),
+            published_at: Some(b.published_at),
+            description: Some(b.description),
+            isbn: Some(b.isbn),
+            total_pages: Some(b.total_pages),
+        },
+        &mut errs,
+    );
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(DomainError::InvalidFields(errs))
+    }
+}
+
+// Longest title or author the books table holds, in characters.
+const MAX_NAME_LEN: usize = 255;
+
+// check_fields validates every field present in the patch.
+fn check_fields(p: &model::BookPatch, errs: &mut Vec<FieldError>) {
+    for (field, value) in [("title", &p.title), ("author", &p.author)] {
+        match value {
+            Some(v) if v.is_empty() => errs.push(FieldError::new(field, "must not be empty")),
+            Some(v) if v.chars().count() > MAX_NAME_LEN => errs.push(FieldError::new(
+                field,
+                format!("must be at most {MAX_NAME_LEN} characters"),
+            )),
+            _ => {}
+        }
+    }
+    if p.published_at
+        .as_ref()
+        .is_some_and(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").is_err())
+    {
+        errs.push(FieldError::new(
+            "published_at",
+            "must be a valid date in YYYY-MM-DD format",
+        ));
+    }
+    if p.isbn.as_ref().is_some_and(|v| !is_valid_isbn(v)) {
+        errs.push(FieldError::new(
+            "isbn",
+            "must be an ISBN-10 or ISBN-13 with a valid check digit",
+        ));
+    }
+    if p.total_pages == Some(0) {
+        errs.push(FieldError::new("total_pages", "must be greater than 0"));
+    }
+}
+
+// normalize_isbn drops the hyphens and spaces an ISBN is often written
+// with, so that it is stored as bare digits.
+fn normalize_isbn(isbn: &str) -> String {
+    isbn.chars()
+        .filter(|c| *c != '-' && *c != ' ')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// is_valid_isbn accepts a normalized ISBN-10 or ISBN-13.
+fn is_valid_isbn(isbn: &str) -> bool {
+    let chars: Vec<char> = isbn.chars().collect();
+    match chars.len() {
+        10 => {
+            let mut sum = 0;
+            for (i, c) in chars.iter().enumerate() {
+                let d = match (i, c) {
+                    (9, 'X') => 10,
+                    (_, c) => match c.to_digit(10) {
+                        Some(d) => d,
+                        None => return false,
+                    },
+                };
+                sum += d * (10 - i as u32);
+            }
+            sum % 11 == 0
+        }
+        13 => {
+            let mut sum = 0;
+            for (i, c) in chars.iter().enumerate() {
+                match c.to_digit(10) {
+                    Some(d) => sum += if i % 2 == 0 { d } else { d * 3 },
+                    None => return false,
+                }
+            }
+            sum % 10 == 0
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::domain::gateway::BookManager;
+    use crate::infrastructure::database::InMemoryBookManager;
+
+    fn body(title: &str, isbn: &str) -> dto::BookCreateBody {
+        dto::BookCreateBody {
+            title: title.to_string(),
+            author: "Someone".to_string(),
+            published_at: "2020-01-01".to_string(),
+            description: String::new(),
+            isbn: isbn.to_string(),
+            total_pages: 10,
+        }
+    }
+
+    fn invalid_fields(body: &dto::BookCreateBody) -> Vec<&'static str> {
+        match validate_book(body) {
+            Err(DomainError::InvalidFields(errs)) => errs.iter().map(|e| e.field).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn isbn_is_stored_without_separators() {
+        assert_eq!(
+            to_model(&body("T", "978-0-306-40615-7")).isbn,
+            "9780306406157"
+        );
+        assert_eq!(to_model(&body("T", "0 8044 2957 x")).isbn, "080442957X");
+        assert!(invalid_fields(&body("T", "978-0-306-40615-7")).is_empty());
+    }
+
+    #[test]
+    fn overlong_title_is_a_field_error() {
+        let title = "a".repeat(MAX_NAME_LEN + 1);
+        assert_eq!(invalid_fields(&body(&title, "9780306406157")), ["title"]);
+        let title = "é".repeat(MAX_NAME_LEN);
+        assert!(invalid_fields(&body(&title, "9780306406157")).is_empty());
     }
 
-    pub fn delete_book(&self, id: u32) -> Result<(), Box<dyn std::error::Error>> {
-        self.book_manager.delete_book(id)
+    fn cached_operator() -> BookOperator {
+        cached_operator_over(Arc::new(InMemoryBookManager::new(10)))
+    }
+
+    fn cached_operator_over(book_manager: Arc<InMemoryBookManager>) -> BookOperator {
+        let policy = cache::Policy {
+            enabled: true,
+            key_prefix: "test-".to_string(),
+            book_list_ttl: Duration::from_secs(3600),
+            book_ttl: Duration::from_secs(3600),
+            reviews_ttl: Duration::from_secs(3600),
+            search_ttl: Duration::from_secs(3600),
+            jitter: 0.0,
+        };
+        BookOperator::new(book_manager, Arc::new(cache::InMemoryCache::new()), &policy)
+    }
+
+    fn titles(op: &BookOperator, query: &str) -> Vec<String> {
+        let books = op.get_books(0, query).unwrap();
+        books.into_iter().map(|b| b.title).collect()
+    }
+
+    #[test]
+    fn list_shows_a_created_book() {
+        let op = cached_operator();
+        op.create_book(&body("First", "9780306406157")).unwrap();
+        assert_eq!(titles(&op, ""), ["First"]);
+        assert!(titles(&op, "second").is_empty());
+
+        op.create_book(&body("Second", "9780306406157")).unwrap();
+        assert_eq!(titles(&op, ""), ["First", "Second"]);
+        assert_eq!(titles(&op, "second"), ["Second"]);
+    }
+
+    #[test]
+    fn list_and_book_show_an_update() {
+        let op = cached_operator();
+        let book = op.create_book(&body("Before", "9780306406157")).unwrap();
+        assert_eq!(titles(&op, ""), ["Before"]);
+        assert_eq!(op.get_book(book.id).unwrap().unwrap().title, "Before");
+
+        op.update_book(book.id, &body("After", "9780306406157"))
+            .unwrap();
+        assert_eq!(titles(&op, ""), ["After"]);
+        assert_eq!(op.get_book(book.id).unwrap().unwrap().title, "After");
+    }
+
+    #[test]
+    fn list_shows_a_patch() {
+        let op = cached_operator();
+        let book = op.create_book(&body("Before", "9780306406157")).unwrap();
+        assert_eq!(titles(&op, ""), ["Before"]);
+
+        let patch = dto::BookPatchBody {
+            title: Some(Some("Patched".to_string())),
+            ..Default::default()
+        };
+        op.patch_book(book.id, &patch).unwrap();
+        assert_eq!(titles(&op, ""), ["Patched"]);
+    }
+
+    #[test]
+    fn list_drops_a_deleted_book() {
+        let op = cached_operator();
+        let book = op.create_book(&body("Doomed", "9780306406157")).unwrap();
+        assert_eq!(titles(&op, ""), ["Doomed"]);
+        assert_eq!(titles(&op, "doomed"), ["Doomed"]);
+
+        op.delete_book(book.id).unwrap();
+        assert!(titles(&op, "").is_empty());
+        assert!(titles(&op, "doomed").is_empty());
+        assert!(op.get_book(book.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn searches_differing_in_case_share_an_entry() {
+        let books = Arc::new(InMemoryBookManager::new(10));
+        let op = cached_operator_over(books.clone());
+        op.create_book(&body("Rust in Action", "9780306406157"))
+            .unwrap();
+        assert_eq!(titles(&op, "Rust"), ["Rust in Action"]);
+
+        // Added behind the cache's back, so only a backend call can find it.
+        books
+            .create_book(&to_model(&body("Rusty Nails", "9780306406157")))
+            .unwrap();
+        assert_eq!(titles(&op, "rUST"), ["Rust in Action"]);
+        assert_eq!(titles(&op, "Rus"), ["Rust in Action", "Rusty Nails"]);
     }
 }
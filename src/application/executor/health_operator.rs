@@ -0,0 +1,168 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::application::dto;
+use crate::infrastructure::health::Dependency;
+
+const UP: &str = "up";
+const DOWN: &str = "down";
+
+pub struct HealthOperator {
+    dependencies: Vec<Dependency>,
+    // Whether a ping of the dependency at the same index is still running.
+    pinging: Vec<Arc<AtomicBool>>,
+    timeout: Duration,
+}
+
+impl HealthOperator {
+    pub fn new(dependencies: Vec<Dependency>, timeout: Duration) -> Self {
+        HealthOperator {
+            pinging: dependencies.iter().map(|_| Arc::default()).collect(),
+            dependencies,
+            timeout,
+        }
+    }
+
+    // readiness pings every dependency in parallel. A ping that does not
+    // answer within the timeout counts as down; its thread is left to finish
+    // on its own, and until it does the dependency is not pinged again.
+    pub fn readiness(&self) -> dto::Readiness {
+        let start = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let mut still_pinging = vec![false; self.dependencies.len()];
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            if self.pinging[i].swap(true, Ordering::AcqRel) {
+                still_pinging[i] = true;
+                continue;
+            }
+            let busy = Busy(self.pinging[i].clone());
+            let (tx, probe, timeout) = (tx.clone(), dep.probe.clone(), self.timeout);
+            thread::spawn(move || {
+                let _busy = busy;
+                let began = Instant::now();
+                let result = probe.ping(timeout).map_err(|e| e.to_string());
+                let _ = tx.send((i, began.elapsed(), result));
+            });
+        }
+        drop(tx);
+
+        let mut components: Vec<dto::ComponentHealth> = self
+            .dependencies
+            .iter()
+            .zip(still_pinging)
+            .map(|(dep, still_pinging)| dto::ComponentHealth {
+                name: dep.name.to_string(),
+                status: DOWN.to_string(),
+                required: dep.required,
+                latency_ms: millis(self.timeout),
+                error: Some(if still_pinging {
+                    "an earlier ping has not answered yet".to_string()
+                } else {
+                    format!("no answer within {}ms", self.timeout.as_millis())
+                }),
+            })
+            .collect();
+        let deadline = start + self.timeout;
+        while let Ok((i, latency, result)) =
+            rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            let c = &mut components[i];
+            c.latency_ms = millis(latency);
+            match result {
+                Ok(()) => {
+                    c.status = UP.to_string();
+                    c.error = None;
+                }
+                Err(e) => c.error = Some(e),
+            }
+        }
+
+        let down = |required: bool| {
+            components
+                .iter()
+                .any(|c| c.required == required && c.status == DOWN)
+        };
+        let status = if down(true) {
+            "unavailable"
+        } else if down(false) {
+            "degraded"
+        } else {
+            "ready"
+        };
+        dto::Readiness {
+            status: status.to_string(),
+            components,
+        }
+    }
+}
+
+// Busy clears a dependency's pinging flag when its ping ends, even by a
+// panic.
+struct Busy(Arc<AtomicBool>);
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    (d.as_secs_f64() * 100_000.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::infrastructure::health::Probe;
+
+    // Hung answers pings only once `release` is dropped.
+    struct Hung {
+        calls: AtomicUsize,
+        release: Mutex<mpsc::Receiver<()>>,
+    }
+
+    impl Probe for Hung {
+        fn ping(&self, _timeout: Duration) -> Result<(), Box<dyn Error>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let _ = self.release.lock().unwrap().recv();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hung_dependency_is_not_pinged_again_until_it_answers() {
+        let (release, rx) = mpsc::channel();
+        let probe = Arc::new(Hung {
+            calls: AtomicUsize::new(0),
+            release: Mutex::new(rx),
+        });
+        let op = HealthOperator::new(
+            vec![Dependency {
+                name: "hung",
+                required: true,
+                probe: probe.clone(),
+            }],
+            Duration::from_millis(20),
+        );
+
+        for _ in 0..3 {
+            let readiness = op.readiness();
+            assert_eq!(readiness.status, "unavailable");
+        }
+        assert_eq!(probe.calls.load(Ordering::SeqCst), 1);
+
+        drop(release);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while op.pinging[0].load(Ordering::Acquire) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(op.readiness().status, "ready");
+        assert_eq!(probe.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,6 +1,14 @@
 mod book_operator;
 pub use book_operator::BookOperator;
 
+mod cache_operator;
+pub use cache_operator::CacheOperator;
+
+mod health_operator;
+pub use health_operator::HealthOperator;
+
+mod read_through;
+
 mod review_operator;
 pub use review_operator::ReviewOperator;
 
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::domain::DomainError;
+use crate::infrastructure::cache;
+
+// ReadThrough caches loader results as JSON. Keys are namespaced by a version
+// counter, so bumping the counter invalidates every key at once and entries
+// cached under an older version simply expire.
+pub struct ReadThrough {
+    cache_helper: Arc<dyn cache::Helper>,
+    policy: cache::Policy,
+    prefix: String,
+    version_key: String,
+    flights: SingleFlight,
+}
+
+impl ReadThrough {
+    pub fn new(cache_helper: Arc<dyn cache::Helper>, policy: &cache::Policy, name: &str) -> Self {
+        let prefix = format!("{}{}", policy.key_prefix, name);
+        ReadThrough {
+            cache_helper,
+            policy: policy.clone(),
+            version_key: version_key(&policy.key_prefix, name),
+            prefix,
+            flights: SingleFlight::default(),
+        }
+    }
+
+    // get returns the cached value for `key`, or runs `load` and caches its
+    // result for about `ttl`. Concurrent misses on one key share a single
+    // `load` call.
+    pub fn get<T, F>(&self, key: &str, ttl: Duration, load: F) -> Result<T, DomainError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T, DomainError>,
+    {
+        if !self.policy.enabled {
+            return load();
+        }
+        let version = self.cache_helper.load(&self.version_key)?;
+        let k = format!(
+            "{}-v{}-{}",
+            self.prefix,
+            version.as_deref().unwrap_or("0"),
+            key
+        );
+        if let Some(v) = self.cache_helper.load(&k)? {
+            return Ok(serde_json::from_str(&v)?);
+        }
+        let v = self.flights.run(&k, || {
+            let v = serde_json::to_string(&load()?)?;
+            self.cache_helper.save(&k, &v, self.policy.jittered(ttl))?;
+            Ok(v)
+        })?;
+        Ok(serde_json::from_str(&v)?)
+    }
+
+    // invalidate never fails the request: the cache replays a bump it could
+    // not apply before serving from the cache again.
+    pub fn invalidate(&self) {
+        if !self.policy.enabled {
+            return;
+        }
+        if let Err(e) = self.cache_helper.incr(&self.version_key) {
+            warn!("failed to invalidate {} cache: {}", self.prefix, e);
+        }
+    }
+}
+
+// query_key is the part of a cache key naming search `query`. Every backend
+// matches ASCII case-insensitively, so queries differing only in that share
+// an entry; anything else may change what they match.
+pub fn query_key(query: &str) -> String {
+    query.to_ascii_lowercase()
+}
+
+// version_key is where the version counter of key space `name` is kept.
+pub fn version_key(key_prefix: &str, name: &str) -> String {
+    format!("{key_prefix}{name}-version")
+}
+
+type FlightResult = Result<String, DomainError>;
+
+// SingleFlight lets the first caller for a key run the load while later
+// callers for the same key wait for it and share its result.
+#[derive(Default)]
+struct SingleFlight {
+    calls: Mutex<HashMap<String, Arc<Flight>>>,
+}
+
+#[derive(Default)]
+struct Flight {
+    result: Mutex<Option<FlightResult>>,
+    done: Condvar,
+}
+
+impl SingleFlight {
+    fn run<F>(&self, key: &str, f: F) -> FlightResult
+    where
+        F: FnOnce() -> FlightResult,
+    {
+        let (flight, leader) = {
+            let mut calls = self.calls.lock().unwrap();
+            match calls.get(key) {
+                Some(flight) => (flight.clone(), false),
+                None => {
+                    let flight = Arc::new(Flight::default());
+                    calls.insert(key.to_string(), flight.clone());
+                    (flight, true)
+                }
+            }
+        };
+        if !leader {
+            let mut result = flight.result.lock().unwrap();
+            while result.is_none() {
+                result = flight.done.wait(result).unwrap();
+            }
+            return result.clone().unwrap();
+        }
+        let landing = Landing {
+            calls: &self.calls,
+            key,
+            flight,
+        };
+        let result = f();
+        *landing.flight.result.lock().unwrap() = Some(result.clone());
+        result
+    }
+}
+
+// Landing wakes the waiting callers when the leader finishes, even if its
+// load panics.
+struct Landing<'a> {
+    calls: &'a Mutex<HashMap<String, Arc<Flight>>>,
+    key: &'a str,
+    flight: Arc<Flight>,
+}
+
+impl Drop for Landing<'_> {
+    fn drop(&mut self) {
+        self.calls.lock().unwrap().remove(self.key);
+        let mut result = self.flight.result.lock().unwrap();
+        if result.is_none() {
+            *result = Some(Err(DomainError::Backend("cache load aborted".to_string())));
+        }
+        self.flight.done.notify_all();
+    }
+}
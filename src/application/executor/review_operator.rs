@@ -2,41 +2,65 @@ use std::sync::Arc;
 
 use chrono::Utc;
 
+use super::read_through::{query_key, ReadThrough};
 use crate::application::dto;
 use crate::domain::gateway;
 use crate::domain::model;
+use crate::domain::{DomainError, FieldError};
+use crate::infrastructure::cache;
+
+// Every review change bumps the version of this key space. One version covers
+// the review lists of all books, so a change takes a single bump.
+pub(super) const REVIEWS_KEY: &str = "reviews";
 
 pub struct ReviewOperator {
     review_manager: Arc<dyn gateway::ReviewManager>,
+    user_manager: Arc<dyn gateway::UserManager>,
+    cache: ReadThrough,
+    policy: cache::Policy,
 }
 
 impl ReviewOperator {
-    pub fn new(b: Arc<dyn gateway::ReviewManager>) -> Self {
-        ReviewOperator { review_manager: b }
+    pub fn new(
+        b: Arc<dyn gateway::ReviewManager>,
+        u: Arc<dyn gateway::UserManager>,
+        c: Arc<dyn cache::Helper>,
+        p: &cache::Policy,
+    ) -> Self {
+        ReviewOperator {
+            review_manager: b,
+            user_manager: u,
+            cache: ReadThrough::new(c, p, REVIEWS_KEY),
+            policy: p.clone(),
+        }
     }
 
     pub fn create_review(
         &self,
         body: &dto::ReviewBody,
-    ) -> Result<model::Review, Box<dyn std::error::Error>> {
+        caller: &model::AuthUser,
+    ) -> Result<model::Review, DomainError> {
+        let user = self
+            .user_manager
+            .get_user(caller.id)?
+            .ok_or_else(|| DomainError::Unauthorized("user does not exist".to_string()))?;
         let now = Utc::now();
         let review = model::Review {
             id: String::new(),
             book_id: body.book_id,
-            author: body.author.clone(),
+            user_id: user.id,
+            author: user.display_name().to_string(),
             title: body.title.clone(),
             content: body.content.clone(),
             created_at: now,
             updated_at: now,
         };
         let id = self.review_manager.create_review(&review)?;
+        self.cache.invalidate();
         Ok(model::Review { id, ..review })
     }
 
-    pub fn get_review(
-        &self,
-        id: &str,
-    ) -> Result<Option<model::Review>, Box<dyn std::error::Error>> {
+    pub fn get_review(&self, id: &str) -> Result<Option<model::Review>, DomainError> {
         self.review_manager.get_review(id)
     }
 
@@ -44,33 +68,121 @@ impl ReviewOperator {
         &self,
         book_id: u32,
         query: &str,
-    ) -> Result<Vec<model::Review>, Box<dyn std::error::Error>> {
-        self.review_manager.get_reviews_of_book(book_id, query)
+    ) -> Result<Vec<model::Review>, DomainError> {
+        let ttl = if query.is_empty() {
+            self.policy.reviews_ttl
+        } else {
+            self.policy.search_ttl
+        };
+        self.cache
+            .get(&format!("{book_id}-q-{}", query_key(query)), ttl, || {
+                self.review_manager.get_reviews_of_book(book_id, query)
+            })
     }
 
     pub fn update_review(
         &self,
         id: &str,
         body: dto::ReviewBody,
-    ) -> Result<model::Review, Box<dyn std::error::Error>> {
+        caller: &model::AuthUser,
+    ) -> Result<model::Review, DomainError> {
         if body.title.is_empty() || body.content.is_empty() {
-            return Err("Required field cannot be empty".into());
+            return Err(DomainError::Validation(
+                "Required field cannot be empty".to_string(),
+            ));
+        }
+        let current = self.owned_review(id, caller)?;
+        // A review stays with the book it was written for.
+        if body.book_id != current.book_id {
+            return Err(DomainError::InvalidFields(vec![FieldError::new(
+                "book_id",
+                "cannot be changed",
+            )]));
         }
-        let now = Utc::now();
         let review = model::Review {
-            id: id.to_string(),
-            book_id: body.book_id,
-            author: body.author.clone(),
             title: body.title.clone(),
             content: body.content.clone(),
-            created_at: now,
-            updated_at: now,
+            updated_at: Utc::now(),
+            ..current
+        };
+        if !self.review_manager.update_review(id, &review)? {
+            return Err(review_not_found(id));
+        }
+        self.cache.invalidate();
+        self.review_manager
+            .get_review(id)?
+            .ok_or_else(|| review_not_found(id))
+    }
+
+    pub fn patch_review(
+        &self,
+        id: &str,
+        body: &dto::ReviewPatchBody,
+        caller: &model::AuthUser,
+    ) -> Result<model::Review, DomainError> {
+        let mut errs = Vec::new();
+        let patch = model::ReviewPatch {
+            title: non_empty("title", &body.title, &mut errs),
+            content: non_empty("content", &body.content, &mut errs),
         };
-        self.review_manager.update_review(id, &review)?;
+        if !errs.is_empty() {
+            return Err(DomainError::InvalidFields(errs));
+        }
+        self.owned_review(id, caller)?;
+        if !self.review_manager.patch_review(id, &patch)? {
+            return Err(review_not_found(id));
+        }
+        self.cache.invalidate();
+        self.review_manager
+            .get_review(id)?
+            .ok_or_else(|| review_not_found(id))
+    }
+
+    pub fn delete_review(&self, id: &str, caller: &model::AuthUser) -> Result<(), DomainError> {
+        self.owned_review(id, caller)?;
+        if !self.review_manager.delete_review(id)? {
+            return Err(review_not_found(id));
+        }
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    // owned_review loads a review that `caller` may change: their own, or
+    // any if they are an admin.
+    fn owned_review(
+        &self,
+        id: &str,
+        caller: &model::AuthUser,
+    ) -> Result<model::Review, DomainError> {
+        let review = self
+            .review_manager
+            .get_review(id)?
+            .ok_or_else(|| review_not_found(id))?;
+        if review.user_id != caller.id && caller.permission < model::UserPermission::Admin {
+            return Err(DomainError::Forbidden(
+                "only the author or an admin may change this review".to_string(),
+            ));
+        }
         Ok(review)
     }
+}
 
-    pub fn delete_review(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.review_manager.delete_review(id)
+fn review_not_found(id: &str) -> DomainError {
+    DomainError::NotFound(format!("review {id} not found"))
+}
+
+// non_empty accepts a supplied field only if it is neither null nor empty.
+fn non_empty(
+    field: &'static str,
+    value: &Option<Option<String>>,
+    errs: &mut Vec<FieldError>,
+) -> Option<String> {
+    match value {
+        Some(Some(v)) if !v.is_empty() => Some(v.clone()),
+        Some(_) => {
+            errs.push(FieldError::new(field, "must not be empty"));
+            None
+        }
+        None => None,
     }
 }
@@ -1,43 +1,51 @@
-use std::error::Error;
-use std::sync::Arc;
-
-use rand::{thread_rng, Rng};
-use sha1::{Digest, Sha1};
+use std::sync::{Arc, OnceLock};
 
 use crate::application::dto;
-use crate::domain::{gateway, model};
+use crate::domain::gateway::PasswordCheck;
+use crate::domain::{gateway, model, DomainError};
 
-const SALT_LEN: usize = 4;
 const ERR_EMPTY_EMAIL: &str = "empty email";
 const ERR_EMPTY_PASSWORD: &str = "empty password";
+// Sign-in failures share one message, so it does not tell which emails are
+// registered.
+const ERR_BAD_CREDENTIALS: &str = "invalid email or password";
 
 pub struct UserOperator {
     user_manager: Arc<dyn gateway::UserManager>,
     perm_manager: Arc<dyn gateway::PermissionManager>,
+    password_manager: Arc<dyn gateway::PasswordManager>,
+    // Checked against when the email is unknown, so that a sign-in takes
+    // as long whether or not the user exists.
+    dummy_hash: OnceLock<String>,
 }
 
 impl UserOperator {
-    pub fn new(u: Arc<dyn gateway::UserManager>, p: Arc<dyn gateway::PermissionManager>) -> Self {
+    pub fn new(
+        u: Arc<dyn gateway::UserManager>,
+        p: Arc<dyn gateway::PermissionManager>,
+        pw: Arc<dyn gateway::PasswordManager>,
+    ) -> Self {
         UserOperator {
             user_manager: u,
             perm_manager: p,
+            password_manager: pw,
+            dummy_hash: OnceLock::new(),
         }
     }
 
-    pub fn create_user(&self, uc: &dto::UserCredential) -> Result<dto::User, Box<dyn Error>> {
+    pub fn create_user(&self, uc: &dto::UserCredential) -> Result<dto::User, DomainError> {
         if uc.email.is_empty() {
-            return Err(ERR_EMPTY_EMAIL.into());
+            return Err(DomainError::Validation(ERR_EMPTY_EMAIL.to_string()));
         }
         if uc.password.is_empty() {
-            return Err(ERR_EMPTY_PASSWORD.into());
+            return Err(DomainError::Validation(ERR_EMPTY_PASSWORD.to_string()));
         }
-        let salt = random_string(SALT_LEN);
         let user = model::User {
             id: 0,
             email: uc.email.clone(),
-            password: sha1_hash(&(uc.password.clone() + &salt)),
-            salt,
-            is_admin: false,
+            password: self.password_manager.hash_password(&uc.password)?,
+            salt: String::new(),
+            role: model::UserPermission::User,
             created_at: chrono::Utc::now()
                 .format("%Y-%m-%d %H:%M:%S%.3f")
                 .to_string(),
@@ -48,62 +56,96 @@ impl UserOperator {
         let uid = self.user_manager.create_user(&user)?;
         Ok(dto::User {
             id: uid,
-            email: uc.email.clone(),
+            ..user.into()
         })
     }
 
-    pub fn sign_in(&self, email: &str, password: &str) -> Result<dto::UserToken, Box<dyn Error>> {
+    pub fn sign_in(&self, email: &str, password: &str) -> Result<dto::UserToken, DomainError> {
         if email.is_empty() {
-            return Err(ERR_EMPTY_EMAIL.into());
+            return Err(DomainError::Validation(ERR_EMPTY_EMAIL.to_string()));
         }
         if password.is_empty() {
-            return Err(ERR_EMPTY_PASSWORD.into());
+            return Err(DomainError::Validation(ERR_EMPTY_PASSWORD.to_string()));
         }
         let user = self.user_manager.get_user_by_email(email)?;
         if let Some(u) = user {
-            let password_hash = sha1_hash(&(password.to_string() + &u.salt));
-            if u.password != password_hash {
-                return Err("wrong password".into());
+            match self
+                .password_manager
+                .verify_password(password, &u.password, &u.salt)?
+            {
+                PasswordCheck::Mismatch => {
+                    return Err(DomainError::Unauthorized(ERR_BAD_CREDENTIALS.to_string()))
+                }
+                PasswordCheck::MatchNeedsRehash => self.rehash(u.id, password),
+                PasswordCheck::Match => {}
             }
-            let perm = if u.is_admin {
-                model::UserPermission::Admin
-            } else {
-                model::UserPermission::User
-            };
-            let token = self.perm_manager.generate_token(u.id, &u.email, perm)?;
+            let tokens = self
+                .perm_manager
+                .generate_tokens(u.id, &u.email, u.role.clone())?;
             Ok(dto::UserToken {
-                user: dto::User {
-                    id: u.id,
-                    email: u.email,
-                },
-                token,
+                user: u.into(),
+                tokens: tokens.into(),
             })
         } else {
-            Err("user does not exist".into())
+            let dummy = self
+                .dummy_hash
+                .get_or_init(|| self.password_manager.hash_password("").unwrap_or_default());
+            let _ = self.password_manager.verify_password(password, dummy, "");
+            Err(DomainError::Unauthorized(ERR_BAD_CREDENTIALS.to_string()))
         }
     }
 
-    pub fn has_permission(
-        &self,
-        token: &str,
-        perm: model::UserPermission,
-    ) -> Result<bool, Box<dyn Error>> {
-        self.perm_manager.has_permission(token, perm)
+    // rehash upgrades a stored hash after a successful sign-in. A failure
+    // only delays the upgrade to the next sign-in.
+    fn rehash(&self, id: u32, password: &str) {
+        let result = self
+            .password_manager
+            .hash_password(password)
+            .and_then(|hash| self.user_manager.update_password(id, &hash, ""));
+        if let Err(e) = result {
+            warn!("failed to upgrade password hash of user {}: {}", id, e);
+        }
     }
-}
 
-fn random_string(length: usize) -> String {
-    let charset = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-    let mut rng = thread_rng();
-    (0..length)
-        .map(|_| rng.gen::<usize>() % charset.len())
-        .map(|idx| charset.chars().nth(idx).unwrap())
-        .collect()
+    pub fn refresh(&self, refresh_token: &str) -> Result<dto::Tokens, DomainError> {
+        if refresh_token.is_empty() {
+            return Err(DomainError::Validation("empty refresh token".to_string()));
+        }
+        let grant = self.perm_manager.redeem_refresh_token(refresh_token)?;
+        // The role may have changed since the chain started.
+        let u = self
+            .user_manager
+            .get_user(grant.user_id)?
+            .ok_or_else(|| DomainError::Unauthorized("user does not exist".to_string()))?;
+        self.perm_manager
+            .continue_chain(&grant, &u.email, u.role)
+            .map(Into::into)
+    }
+
+    pub fn sign_out(&self, token: &str) -> Result<(), DomainError> {
+        self.perm_manager.revoke_tokens(token)
+    }
+
+    pub fn get_users(&self, offset: u32) -> Result<Vec<dto::User>, DomainError> {
+        let users = self.user_manager.get_users(offset)?;
+        Ok(users.into_iter().map(Into::into).collect())
+    }
+
+    pub fn update_role(&self, id: u32, body: &dto::RoleBody) -> Result<dto::User, DomainError> {
+        if !self.user_manager.update_role(id, &body.role)? {
+            return Err(user_not_found(id));
+        }
+        self.user_manager
+            .get_user(id)?
+            .map(Into::into)
+            .ok_or_else(|| user_not_found(id))
+    }
+
+    pub fn authenticate(&self, token: &str) -> Result<model::AuthUser, DomainError> {
+        self.perm_manager.authenticate(token)
+    }
 }
 
-fn sha1_hash(input: &str) -> String {
-    let mut h = Sha1::new();
-    h.update(input);
-    let hash_bytes = h.finalize();
-    hex::encode(hash_bytes)
+fn user_not_found(id: u32) -> DomainError {
+    DomainError::NotFound(format!("user {id} not found"))
 }
@@ -1,56 +1,270 @@
+use std::error::Error;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::domain::gateway;
 use crate::infrastructure::cache;
-use crate::infrastructure::database;
+use crate::infrastructure::database::{self, migration, Migrator};
+use crate::infrastructure::health::Dependency;
+use crate::infrastructure::password;
 use crate::infrastructure::token;
-use crate::infrastructure::Config;
+use crate::infrastructure::{CacheBackend, Config, DatabaseBackend, ReviewBackend};
+
+// NamedMigrator pairs a migrator with the database kind it manages.
+pub type NamedMigrator = (&'static str, Arc<dyn Migrator>);
 
 pub struct WireHelper {
-    sql_persistence: Arc<database::MySQLPersistence>,
-    no_sql_persistence: Arc<database::MongoPersistence>,
-    kv_store: Arc<cache::RedisCache>,
+    book_manager: Arc<dyn gateway::BookManager>,
+    user_manager: Arc<dyn gateway::UserManager>,
+    review_manager: Arc<dyn gateway::ReviewManager>,
+    kv_store: Arc<dyn cache::Helper>,
+    cache_policy: cache::Policy,
     token_keeper: Arc<token::Keeper>,
+    password_hasher: Arc<password::Hasher>,
+    dependencies: Vec<Dependency>,
+    health_timeout: Duration,
 }
 
 impl WireHelper {
-    pub fn new(c: &Config) -> Result<Self, Box<dyn std::error::Error>> {
-        let sql_persistence =
-            Arc::new(database::MySQLPersistence::new(&c.db.dsn, c.app.page_size)?);
-        let no_sql_persistence = Arc::new(database::MongoPersistence::new(
-            &c.db.mongo_uri,
-            &c.db.mongo_db_name,
-        )?);
-        let kv_store = Arc::new(cache::RedisCache::new(&c.cache.redis_uri)?);
+    pub fn new(c: &Config) -> Result<Self, Box<dyn Error>> {
+        let mut backends = Backends::new(c);
+        let book_manager: Arc<dyn gateway::BookManager> = match c.backend.book {
+            DatabaseBackend::MySQL => backends.mysql()?,
+            DatabaseBackend::SQLite => backends.sqlite()?,
+            DatabaseBackend::Memory => {
+                Arc::new(database::InMemoryBookManager::new(c.app.page_size))
+            }
+        };
+        let user_manager: Arc<dyn gateway::UserManager> = match c.backend.user {
+            DatabaseBackend::MySQL => backends.mysql()?,
+            DatabaseBackend::SQLite => backends.sqlite()?,
+            DatabaseBackend::Memory => {
+                Arc::new(database::InMemoryUserManager::new(c.app.page_size))
+            }
+        };
+        let review_manager: Arc<dyn gateway::ReviewManager> = match c.backend.review {
+            ReviewBackend::Mongo => backends.mongo()?,
+            ReviewBackend::MySQL => backends.mysql()?,
+            ReviewBackend::SQLite => backends.sqlite()?,
+            ReviewBackend::Memory => Arc::new(database::InMemoryReviewManager::new()),
+        };
+        let mut dependencies = backends.dependencies();
+        // Revocations skip the fail-safe and local layers: a sign-out must not
+        // be lost, nor a revoked token honored, because Redis is down.
+        let (kv_store, revocations): (Arc<dyn cache::Helper>, Arc<dyn cache::Helper>) =
+            match c.backend.cache {
+                CacheBackend::Redis => {
+                    let redis = Arc::new(cache::RedisCache::new(
+                        &c.cache.redis_uri,
+                        cache::RedisOptions {
+                            pool_size: c.cache.pool_size,
+                            connect_timeout: Duration::from_millis(c.cache.connect_timeout_ms),
+                            io_timeout: Duration::from_millis(c.cache.io_timeout_ms),
+                        },
+                    )?);
+                    // Anonymous reads are still served while Redis is down, only
+                    // slower, but requests with a token fail unless they are
+                    // let through unchecked.
+                    dependencies.push(Dependency {
+                        name: "redis",
+                        required: !c.app.auth_fail_open,
+                        probe: redis.clone(),
+                    });
+                    let fail_safe = Arc::new(cache::FailSafeCache::new(
+                        c.cache.breaker_threshold,
+                        Duration::from_millis(c.cache.breaker_cooldown_ms),
+                        redis.clone(),
+                    ));
+                    let kv_store: Arc<dyn cache::Helper> =
+                        match NonZeroUsize::new(c.cache.local_size) {
+                            Some(size) => Arc::new(cache::LayeredCache::new(
+                                size,
+                                Duration::from_secs(c.cache.local_ttl),
+                                fail_safe,
+                            )),
+                            None => fail_safe,
+                        };
+                    (kv_store, redis)
+                }
+                CacheBackend::Memory => {
+                    let memory = Arc::new(cache::InMemoryCache::new());
+                    (memory.clone(), memory)
+                }
+            };
+        // A SQLite file is private to this process, so it is always migrated.
+        if let Some(p) = &backends.sqlite {
+            migration::migrate_up(p.as_ref())?;
+        }
+        if c.db.auto_migrate {
+            if let Some(p) = &backends.mysql {
+                migration::migrate_up(p.as_ref())?;
+            }
+        }
+        let cache_policy = cache::Policy {
+            enabled: c.cache.enabled,
+            key_prefix: c.cache.key_prefix.clone(),
+            book_list_ttl: Duration::from_secs(c.cache.book_list_ttl),
+            book_ttl: Duration::from_secs(c.cache.book_ttl),
+            reviews_ttl: Duration::from_secs(c.cache.reviews_ttl),
+            search_ttl: Duration::from_secs(c.cache.search_ttl),
+            jitter: c.cache.ttl_jitter,
+        };
         let token_keeper = Arc::new(token::Keeper::new(
             c.app.token_secret.clone(),
+            c.app.access_token_minutes,
             c.app.token_hours,
+            revocations,
+            &c.cache.key_prefix,
+            c.app.auth_fail_open,
         ));
         Ok(WireHelper {
-            sql_persistence,
-            no_sql_persistence,
+            book_manager,
+            user_manager,
+            review_manager,
             kv_store,
+            cache_policy,
             token_keeper,
+            password_hasher: Arc::new(password::Hasher::new(
+                c.password.memory_kib,
+                c.password.iterations,
+                c.password.parallelism,
+            )?),
+            dependencies,
+            health_timeout: Duration::from_millis(c.app.health_timeout_ms),
         })
     }
 
+    // migrators opens every relational database the config selects.
+    pub fn migrators(c: &Config) -> Result<Vec<NamedMigrator>, Box<dyn Error>> {
+        let mut backends = Backends::new(c);
+        let mut migrators: Vec<NamedMigrator> = Vec::new();
+        let uses = |b: DatabaseBackend| c.backend.book == b || c.backend.user == b;
+        if uses(DatabaseBackend::MySQL) || c.backend.review == ReviewBackend::MySQL {
+            migrators.push(("mysql", backends.mysql()?));
+        }
+        if uses(DatabaseBackend::SQLite) || c.backend.review == ReviewBackend::SQLite {
+            migrators.push(("sqlite", backends.sqlite()?));
+        }
+        Ok(migrators)
+    }
+
     pub fn book_manager(&self) -> Arc<dyn gateway::BookManager> {
-        Arc::clone(&self.sql_persistence) as Arc<dyn gateway::BookManager>
+        Arc::clone(&self.book_manager)
     }
 
     pub fn user_manager(&self) -> Arc<dyn gateway::UserManager> {
-        Arc::clone(&self.sql_persistence) as Arc<dyn gateway::UserManager>
+        Arc::clone(&self.user_manager)
     }
 
     pub fn perm_manager(&self) -> Arc<dyn gateway::PermissionManager> {
         Arc::clone(&self.token_keeper) as Arc<dyn gateway::PermissionManager>
     }
 
+    pub fn password_manager(&self) -> Arc<dyn gateway::PasswordManager> {
+        Arc::clone(&self.password_hasher) as Arc<dyn gateway::PasswordManager>
+    }
+
     pub fn review_manager(&self) -> Arc<dyn gateway::ReviewManager> {
-        Arc::clone(&self.no_sql_persistence) as Arc<dyn gateway::ReviewManager>
+        Arc::clone(&self.review_manager)
     }
 
     pub fn cache_helper(&self) -> Arc<dyn cache::Helper> {
-        Arc::clone(&self.kv_store) as Arc<dyn cache::Helper>
+        Arc::clone(&self.kv_store)
+    }
+
+    pub fn cache_policy(&self) -> &cache::Policy {
+        &self.cache_policy
+    }
+
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        self.dependencies.clone()
+    }
+
+    pub fn health_timeout(&self) -> Duration {
+        self.health_timeout
+    }
+}
+
+// Backends opens each persistence at most once, so gateways that pick
+// the same backend share one connection pool.
+struct Backends<'a> {
+    c: &'a Config,
+    mysql: Option<Arc<database::MySQLPersistence>>,
+    sqlite: Option<Arc<database::SQLitePersistence>>,
+    mongo: Option<Arc<database::MongoPersistence>>,
+}
+
+impl<'a> Backends<'a> {
+    fn new(c: &'a Config) -> Self {
+        Backends {
+            c,
+            mysql: None,
+            sqlite: None,
+            mongo: None,
+        }
+    }
+
+    fn mysql(&mut self) -> Result<Arc<database::MySQLPersistence>, Box<dyn Error>> {
+        if let Some(p) = &self.mysql {
+            return Ok(Arc::clone(p));
+        }
+        let p = Arc::new(database::MySQLPersistence::new(
+            &self.c.db.dsn,
+            self.c.app.page_size,
+        )?);
+        self.mysql = Some(Arc::clone(&p));
+        Ok(p)
+    }
+
+    fn sqlite(&mut self) -> Result<Arc<database::SQLitePersistence>, Box<dyn Error>> {
+        if let Some(p) = &self.sqlite {
+            return Ok(Arc::clone(p));
+        }
+        let p = Arc::new(database::SQLitePersistence::new(
+            &self.c.db.file_name,
+            self.c.app.page_size,
+        )?);
+        self.sqlite = Some(Arc::clone(&p));
+        Ok(p)
+    }
+
+    // dependencies lists the databases opened so far.
+    fn dependencies(&self) -> Vec<Dependency> {
+        let mut deps = Vec::new();
+        if let Some(p) = &self.mysql {
+            deps.push(Dependency {
+                name: "mysql",
+                required: true,
+                probe: p.clone(),
+            });
+        }
+        if let Some(p) = &self.sqlite {
+            deps.push(Dependency {
+                name: "sqlite",
+                required: true,
+                probe: p.clone(),
+            });
+        }
+        if let Some(p) = &self.mongo {
+            deps.push(Dependency {
+                name: "mongo",
+                required: true,
+                probe: p.clone(),
+            });
+        }
+        deps
+    }
+
+    fn mongo(&mut self) -> Result<Arc<database::MongoPersistence>, Box<dyn Error>> {
+        if let Some(p) = &self.mongo {
+            return Ok(Arc::clone(p));
+        }
+        let p = Arc::new(database::MongoPersistence::new(
+            &self.c.db.mongo_uri,
+            &self.c.db.mongo_db_name,
+        )?);
+        self.mongo = Some(Arc::clone(&p));
+        Ok(p)
     }
 }
@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::fmt;
+
+// DomainError is the error type shared by gateways and operators.
+#[derive(Debug, Clone)]
+pub enum DomainError {
+    NotFound(String),
+    Validation(String),
+    InvalidFields(Vec<FieldError>),
+    Conflict(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Backend(String),
+}
+
+// FieldError describes one invalid field of a request body.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        FieldError {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl DomainError {
+    // code is a stable, machine-readable name for the variant.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DomainError::NotFound(_) => "not_found",
+            DomainError::Validation(_) | DomainError::InvalidFields(_) => "validation_failed",
+            DomainError::Conflict(_) => "conflict",
+            DomainError::Unauthorized(_) => "unauthorized",
+            DomainError::Forbidden(_) => "forbidden",
+            DomainError::Backend(_) => "backend_unavailable",
+        }
+    }
+}
+
+impl fmt::Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainError::NotFound(msg)
+            | DomainError::Validation(msg)
+            | DomainError::Conflict(msg)
+            | DomainError::Unauthorized(msg)
+            | DomainError::Forbidden(msg)
+            | DomainError::Backend(msg) => f.write_str(msg),
+            DomainError::InvalidFields(errs) => {
+                let fields: Vec<&str> = errs.iter().map(|e| e.field).collect();
+                write!(f, "invalid fields: {}", fields.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for DomainError {}
+
+impl From<Box<dyn Error>> for DomainError {
+    fn from(err: Box<dyn Error>) -> Self {
+        DomainError::Backend(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for DomainError {
+    fn from(err: serde_json::Error) -> Self {
+        DomainError::Backend(err.to_string())
+    }
+}
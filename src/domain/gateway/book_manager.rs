@@ -1,11 +1,12 @@
-use std::error::Error;
-
-use crate::domain::model;
+use crate::domain::{model, DomainError};
 
+// update_book, patch_book and delete_book report whether a book with the
+// id existed.
 pub trait BookManager: Send + Sync {
-    fn create_book(&self, b: &model::Book) -> Result<u32, Box<dyn Error>>;
-    fn update_book(&self, id: u32, b: &model::Book) -> Result<(), Box<dyn Error>>;
-    fn delete_book(&self, id: u32) -> Result<(), Box<dyn Error>>;
-    fn get_book(&self, id: u32) -> Result<Option<model::Book>, Box<dyn Error>>;
-    fn get_books(&self, offset: u32, keyword: &str) -> Result<Vec<model::Book>, Box<dyn Error>>;
+    fn create_book(&self, b: &model::Book) -> Result<u32, DomainError>;
+    fn update_book(&self, id: u32, b: &model::Book) -> Result<bool, DomainError>;
+    fn patch_book(&self, id: u32, p: &model::BookPatch) -> Result<bool, DomainError>;
+    fn delete_book(&self, id: u32) -> Result<bool, DomainError>;
+    fn get_book(&self, id: u32) -> Result<Option<model::Book>, DomainError>;
+    fn get_books(&self, offset: u32, keyword: &str) -> Result<Vec<model::Book>, DomainError>;
 }
@@ -5,4 +5,4 @@ mod review_manager;
 pub use review_manager::ReviewManager;
 
 mod user_manager;
-pub use user_manager::{PermissionManager, UserManager};
+pub use user_manager::{PasswordCheck, PasswordManager, PermissionManager, UserManager};
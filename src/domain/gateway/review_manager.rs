@@ -1,15 +1,16 @@
-use std::error::Error;
-
-use crate::domain::model;
+use crate::domain::{model, DomainError};
 
+// update_review, patch_review and delete_review report whether a review
+// with the id existed.
 pub trait ReviewManager: Send + Sync {
-    fn create_review(&self, b: &model::Review) -> Result<String, Box<dyn Error>>;
-    fn update_review(&self, id: &str, b: &model::Review) -> Result<(), Box<dyn Error>>;
-    fn delete_review(&self, id: &str) -> Result<(), Box<dyn Error>>;
-    fn get_review(&self, id: &str) -> Result<Option<model::Review>, Box<dyn Error>>;
+    fn create_review(&self, b: &model::Review) -> Result<String, DomainError>;
+    fn update_review(&self, id: &str, b: &model::Review) -> Result<bool, DomainError>;
+    fn patch_review(&self, id: &str, p: &model::ReviewPatch) -> Result<bool, DomainError>;
+    fn delete_review(&self, id: &str) -> Result<bool, DomainError>;
+    fn get_review(&self, id: &str) -> Result<Option<model::Review>, DomainError>;
     fn get_reviews_of_book(
         &self,
         book_id: u32,
         keyword: &str,
-    ) -> Result<Vec<model::Review>, Box<dyn Error>>;
+    ) -> Result<Vec<model::Review>, DomainError>;
 }
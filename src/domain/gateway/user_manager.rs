@@ -1,23 +1,63 @@
-use std::error::Error;
-
-use crate::domain::model;
+use crate::domain::{model, DomainError};
 
 pub trait UserManager: Send + Sync {
-    fn create_user(&self, u: &model::User) -> Result<u32, Box<dyn Error>>;
-    fn get_user_by_email(&self, email: &str) -> Result<Option<model::User>, Box<dyn Error>>;
+    fn create_user(&self, u: &model::User) -> Result<u32, DomainError>;
+    fn get_user(&self, id: u32) -> Result<Option<model::User>, DomainError>;
+    fn get_user_by_email(&self, email: &str) -> Result<Option<model::User>, DomainError>;
+    fn get_users(&self, offset: u32) -> Result<Vec<model::User>, DomainError>;
+    fn update_role(&self, id: u32, role: &model::UserPermission) -> Result<bool, DomainError>;
+    fn update_password(&self, id: u32, password: &str, salt: &str) -> Result<(), DomainError>;
 }
 
 pub trait PermissionManager: Send + Sync {
-    fn generate_token(
+    // generate_tokens starts a new refresh chain for a signed-in user.
+    fn generate_tokens(
         &self,
         user_id: u32,
         email: &str,
         perm: model::UserPermission,
-    ) -> Result<String, Box<dyn Error>>;
+    ) -> Result<model::TokenPair, DomainError>;
+
+    // redeem_refresh_token uses up a refresh token, which works only once.
+    fn redeem_refresh_token(&self, refresh_token: &str)
+        -> Result<model::RefreshGrant, DomainError>;
 
-    fn has_permission(
+    // continue_chain issues the pair a redeemed refresh token was exchanged
+    // for, carrying the user's current email and role.
+    fn continue_chain(
         &self,
-        token: &str,
+        grant: &model::RefreshGrant,
+        email: &str,
         perm: model::UserPermission,
-    ) -> Result<bool, Box<dyn Error>>;
+    ) -> Result<model::TokenPair, DomainError>;
+
+    // revoke_tokens revokes an access token and every token of its chain.
+    fn revoke_tokens(&self, access_token: &str) -> Result<(), DomainError>;
+
+    // authenticate tells who a valid, unrevoked access token belongs to.
+    fn authenticate(&self, access_token: &str) -> Result<model::AuthUser, DomainError>;
+}
+
+// PasswordCheck is the outcome of verifying a password against its hash.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Mismatch,
+    Match,
+    // The password matches, but the hash uses an outdated scheme or cost
+    // and should be replaced.
+    MatchNeedsRehash,
+}
+
+pub trait PasswordManager: Send + Sync {
+    // hash_password returns a self-describing hash, so no separate salt.
+    fn hash_password(&self, password: &str) -> Result<String, DomainError>;
+
+    // verify_password compares in constant time. `salt` is only used by
+    // legacy hashes that keep it in a separate column.
+    fn verify_password(
+        &self,
+        password: &str,
+        hash: &str,
+        salt: &str,
+    ) -> Result<PasswordCheck, DomainError>;
 }
@@ -1,2 +1,5 @@
 pub mod gateway;
 pub mod model;
+
+mod error;
+pub use error::{DomainError, FieldError};
@@ -10,3 +10,15 @@ pub struct Book {
     pub created_at: String,
     pub updated_at: String,
 }
+
+// BookPatch holds the fields a partial update changes; `None` keeps the
+// stored value.
+#[derive(Debug, Clone, Default)]
+pub struct BookPatch {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub published_at: Option<String>,
+    pub description: Option<String>,
+    pub isbn: Option<String>,
+    pub total_pages: Option<u32>,
+}
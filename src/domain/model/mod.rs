@@ -1,8 +1,8 @@
 mod book;
-pub use book::Book;
+pub use book::{Book, BookPatch};
 
 mod review;
-pub use review::Review;
+pub use review::{Review, ReviewPatch};
 
 mod user;
-pub use user::{User, UserPermission};
+pub use user::{AuthUser, RefreshGrant, TokenPair, User, UserPermission};
@@ -4,9 +4,22 @@ use chrono::{DateTime, Utc};
 pub struct Review {
     pub id: String,
     pub book_id: u32,
+    // Id of the user who wrote the review; 0 for reviews from before
+    // reviews had owners.
+    #[serde(default)]
+    pub user_id: u32,
+    // Display name of the user who wrote the review.
     pub author: String,
     pub title: String,
     pub content: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+// ReviewPatch holds the fields a partial update changes; `None` keeps the
+// stored value.
+#[derive(Debug, Clone, Default)]
+pub struct ReviewPatch {
+    pub title: Option<String>,
+    pub content: Option<String>,
+}
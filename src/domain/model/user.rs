@@ -1,5 +1,5 @@
 // UserPermission represents different levels of user permissions.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub enum UserPermission {
     None,
     User,
@@ -7,13 +7,73 @@ pub enum UserPermission {
     Admin,
 }
 
+impl UserPermission {
+    // as_str names the role the way it is stored and serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserPermission::None => "None",
+            UserPermission::User => "User",
+            UserPermission::Author => "Author",
+            UserPermission::Admin => "Admin",
+        }
+    }
+}
+
+impl std::str::FromStr for UserPermission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "None" => Ok(UserPermission::None),
+            "User" => Ok(UserPermission::User),
+            "Author" => Ok(UserPermission::Author),
+            "Admin" => Ok(UserPermission::Admin),
+            _ => Err(format!("unknown role {s:?}")),
+        }
+    }
+}
+
+// TokenPair is what a sign-in or a refresh hands out: a short-lived access
+// token and the refresh token that replaces it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    // Seconds until the access token expires.
+    pub expires_in: u64,
+}
+
+// RefreshGrant is what a redeemed refresh token entitles to: new tokens
+// for the same user, continuing the same chain.
+#[derive(Debug, Clone)]
+pub struct RefreshGrant {
+    pub user_id: u32,
+    pub chain: String,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct User {
     pub id: u32,
     pub email: String,
     pub password: String,
     pub salt: String,
-    pub is_admin: bool,
+    pub role: UserPermission,
     pub created_at: String,
     pub updated_at: String,
 }
+
+impl User {
+    // display_name is how the user is shown to others: the part of the email
+    // before the @, so that addresses are not published.
+    pub fn display_name(&self) -> &str {
+        self.email.split('@').next().unwrap_or_default()
+    }
+}
+
+// AuthUser is the caller an access token was issued to.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuthUser {
+    pub id: u32,
+    pub email: String,
+    pub permission: UserPermission,
+}
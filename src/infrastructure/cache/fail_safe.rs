@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::infrastructure::cache::{Helper, TierStats};
+
+// FailSafeCache keeps a cache outage from failing requests. Errors from the
+// wrapped cache are logged, a failed `load` reads as a miss and a failed
+// `save` is dropped. After `threshold` consecutive failures the breaker opens
+// and the cache is skipped entirely for `cooldown`; the next call after that
+// probes it once and closes the breaker on success.
+pub struct FailSafeCache {
+    remote: Arc<dyn Helper>,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+    // Counters that could not be bumped while the cache was failing. They
+    // are bumped before the cache is read again, so entries written before
+    // the outage are never served after it.
+    pending: HashSet<String>,
+}
+
+impl FailSafeCache {
+    pub fn new(threshold: u32, cooldown: Duration, remote: Arc<dyn Helper>) -> Self {
+        FailSafeCache {
+            remote,
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    // available tells whether the wrapped cache may be called now, replaying
+    // missed counter bumps first.
+    fn available(&self) -> bool {
+        let pending = {
+            let mut s = self.state.lock().unwrap();
+            if let Some(open_until) = s.open_until {
+                if s.probing || Instant::now() < open_until {
+                    return false;
+                }
+                s.probing = true;
+            }
+            std::mem::take(&mut s.pending)
+        };
+        for (i, key) in pending.iter().enumerate() {
+            if let Err(e) = self.remote.incr(key) {
+                self.failed("incr", key, e);
+                self.state
+                    .lock()
+                    .unwrap()
+                    .pending
+                    .extend(pending.into_iter().skip(i));
+                return false;
+            }
+        }
+        true
+    }
+
+    fn succeeded(&self) {
+        let mut s = self.state.lock().unwrap();
+        if s.open_until.is_some() {
+            info!("cache is reachable again, closing circuit breaker");
+        }
+        s.failures = 0;
+        s.open_until = None;
+        s.probing = false;
+    }
+
+    fn failed(&self, op: &str, key: &str, err: Box<dyn Error>) {
+        warn!("cache {} of {:?} failed: {}", op, key, err);
+        let mut s = self.state.lock().unwrap();
+        s.failures += 1;
+        if s.probing || s.failures >= self.threshold {
+            if s.open_until.is_none() {
+                warn!(
+                    "cache failed {} times in a row, bypassing it for {:?}",
+                    s.failures, self.cooldown
+                );
+            }
+            s.open_until = Some(Instant::now() + self.cooldown);
+            s.probing = false;
+        }
+    }
+}
+
+impl Helper for FailSafeCache {
+    fn save(&self, key: &str, value: &str, ttl: Duration) -> Result<(), Box<dyn Error>> {
+        if self.available() {
+            match self.remote.save(key, value, ttl) {
+                Ok(()) => self.succeeded(),
+                Err(e) => self.failed("save", key, e),
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        if !self.available() {
+            return Ok(None);
+        }
+        match self.remote.load(key) {
+            Ok(v) => {
+                self.succeeded();
+                Ok(v)
+            }
+            Err(e) => {
+                self.failed("load", key, e);
+                Ok(None)
+            }
+        }
+    }
+
+    // add fails rather than pretending to have saved, since callers use it
+    // to decide who may proceed.
+    fn add(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, Box<dyn Error>> {
+        if !self.available() {
+            return Err("cache unavailable".into());
+        }
+        match self.remote.add(key, value, ttl) {
+            Ok(added) => {
+                self.succeeded();
+                Ok(added)
+            }
+            Err(e) => {
+                let msg = e.to_string();
+                self.failed("add", key, e);
+                Err(msg.into())
+            }
+        }
+    }
+
+    fn incr(&self, key: &str) -> Result<i64, Box<dyn Error>> {
+        if !self.available() {
+            self.state.lock().unwrap().pending.insert(key.to_string());
+            return Err("cache unavailable".into());
+        }
+        match self.remote.incr(key) {
+            Ok(n) => {
+                self.succeeded();
+                Ok(n)
+            }
+            Err(e) => {
+                let msg = e.to_string();
+                self.failed("incr", key, e);
+                self.state.lock().unwrap().pending.insert(key.to_string());
+                Err(msg.into())
+            }
+        }
+    }
+
+    fn flush(&self, prefix: &str, keep: &[&str]) -> Result<u64, Box<dyn Error>> {
+        if !self.available() {
+            return Err("cache unavailable".into());
+        }
+        match self.remote.flush(prefix, keep) {
+            Ok(n) => {
+                self.succeeded();
+                Ok(n)
+            }
+            Err(e) => {
+                let msg = e.to_string();
+                self.failed("flush", prefix, e);
+                Err(msg.into())
+            }
+        }
+    }
+
+    fn stats(&self) -> Vec<TierStats> {
+        self.remote.stats()
+    }
+}
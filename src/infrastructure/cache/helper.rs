@@ -1,6 +1,51 @@
 use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 pub trait Helper: Send + Sync {
-    fn save(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>>;
+    fn save(&self, key: &str, value: &str, ttl: Duration) -> Result<(), Box<dyn Error>>;
     fn load(&self, key: &str) -> Result<Option<String>, Box<dyn Error>>;
+    // add saves `value` only if `key` holds nothing yet, and tells whether it
+    // did. Concurrent callers can rely on exactly one of them winning.
+    fn add(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, Box<dyn Error>>;
+    // incr atomically bumps a counter that never expires and returns its new value.
+    fn incr(&self, key: &str) -> Result<i64, Box<dyn Error>>;
+    // flush removes every entry whose key starts with `prefix`, except the
+    // keys in `keep`, and returns how many were removed.
+    fn flush(&self, prefix: &str, keep: &[&str]) -> Result<u64, Box<dyn Error>>;
+    // stats reports hit/miss counts for every tier, nearest tier first.
+    fn stats(&self) -> Vec<TierStats>;
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TierStats {
+    pub tier: &'static str,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+// HitCounter counts `load` results of one cache tier.
+#[derive(Default)]
+pub struct HitCounter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl HitCounter {
+    pub fn record<T>(&self, result: &Option<T>) {
+        let counter = if result.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, tier: &'static str) -> TierStats {
+        TierStats {
+            tier,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
 }
@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+
+use crate::infrastructure::cache::{Helper, HitCounter, TierStats};
+
+// LayeredCache keeps a bounded in-process LRU in front of a shared cache.
+// Entries live in the LRU for at most `ttl`, which bounds how long a change
+// made through another instance can stay invisible here.
+pub struct LayeredCache {
+    local: Mutex<LruCache<String, (String, Instant)>>,
+    ttl: Duration,
+    counter: HitCounter,
+    remote: Arc<dyn Helper>,
+}
+
+impl LayeredCache {
+    pub fn new(capacity: NonZeroUsize, ttl: Duration, remote: Arc<dyn Helper>) -> Self {
+        LayeredCache {
+            local: Mutex::new(LruCache::new(capacity)),
+            ttl,
+            counter: HitCounter::default(),
+            remote,
+        }
+    }
+
+    fn load_local(&self, key: &str) -> Option<String> {
+        let mut local = self.local.lock().unwrap();
+        match local.get(key) {
+            Some((v, expire_at)) if *expire_at > Instant::now() => Some(v.clone()),
+            Some(_) => {
+                local.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn save_local(&self, key: &str, value: &str, ttl: Duration) {
+        let expire_at = Instant::now() + ttl.min(self.ttl);
+        self.local
+            .lock()
+            .unwrap()
+            .put(key.to_string(), (value.to_string(), expire_at));
+    }
+}
+
+impl Helper for LayeredCache {
+    fn save(&self, key: &str, value: &str, ttl: Duration) -> Result<(), Box<dyn Error>> {
+        self.remote.save(key, value, ttl)?;
+        self.save_local(key, value, ttl);
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let local = self.load_local(key);
+        self.counter.record(&local);
+        if local.is_some() {
+            return Ok(local);
+        }
+        let remote = self.remote.load(key)?;
+        if let Some(v) = &remote {
+            self.save_local(key, v, self.ttl);
+        }
+        Ok(remote)
+    }
+
+    fn add(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, Box<dyn Error>> {
+        self.remote.add(key, value, ttl)
+    }
+
+    fn incr(&self, key: &str) -> Result<i64, Box<dyn Error>> {
+        self.local.lock().unwrap().pop(key);
+        self.remote.incr(key)
+    }
+
+    fn flush(&self, prefix: &str, keep: &[&str]) -> Result<u64, Box<dyn Error>> {
+        {
+            let mut local = self.local.lock().unwrap();
+            let keys: Vec<String> = local
+                .iter()
+                .map(|(k, _)| k)
+                .filter(|k| k.starts_with(prefix) && !keep.contains(&k.as_str()))
+                .cloned()
+                .collect();
+            for k in keys {
+                local.pop(&k);
+            }
+        }
+        self.remote.flush(prefix, keep)
+    }
+
+    fn stats(&self) -> Vec<TierStats> {
+        let mut stats = vec![self.counter.snapshot("local")];
+        stats.extend(self.remote.stats());
+        stats
+    }
+}
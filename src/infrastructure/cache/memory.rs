@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::infrastructure::cache::{Helper, HitCounter, TierStats};
+
+// Size the map may grow to before expired entries that were never read
+// again are swept out.
+const MIN_SWEEP_SIZE: usize = 1024;
+
+// InMemoryCache is an in-process replacement for RedisCache. Expired
+// entries are dropped when read, and the rest whenever the map has doubled
+// since the last sweep.
+pub struct InMemoryCache {
+    entries: RwLock<HashMap<String, (String, Option<Instant>)>>,
+    sweep_at: AtomicUsize,
+    counter: HitCounter,
+}
+
+impl Default for InMemoryCache {
+    fn default() -> Self {
+        InMemoryCache {
+            entries: RwLock::new(HashMap::new()),
+            sweep_at: AtomicUsize::new(MIN_SWEEP_SIZE),
+            counter: HitCounter::default(),
+        }
+    }
+}
+
+impl InMemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&self, key: &str, value: &str, ttl: Duration) {
+        let expire_at = Instant::now() + ttl;
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= self.sweep_at.load(Ordering::Relaxed) {
+            entries.retain(|_, (_, t)| !is_expired(t));
+            let next = (entries.len() * 2).max(MIN_SWEEP_SIZE);
+            self.sweep_at.store(next, Ordering::Relaxed);
+        }
+        entries.insert(key.to_string(), (value.to_string(), Some(expire_at)));
+    }
+}
+
+impl Helper for InMemoryCache {
+    fn save(&self, key: &str, value: &str, ttl: Duration) -> Result<(), Box<dyn Error>> {
+        self.insert(key, value, ttl);
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let (result, expired) = match self.entries.read().unwrap().get(key) {
+            Some((_, t)) if is_expired(t) => (None, true),
+            Some((v, _)) => (Some(v.clone()), false),
+            None => (None, false),
+        };
+        if expired {
+            let mut entries = self.entries.write().unwrap();
+            if entries.get(key).is_some_and(|(_, t)| is_expired(t)) {
+                entries.remove(key);
+            }
+        }
+        self.counter.record(&result);
+        Ok(result)
+    }
+
+    fn add(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, Box<dyn Error>> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.get(key).is_some_and(|(_, t)| !is_expired(t)) {
+            return Ok(false);
+        }
+        let expire_at = Instant::now() + ttl;
+        entries.insert(key.to_string(), (value.to_string(), Some(expire_at)));
+        Ok(true)
+    }
+
+    // incr counts from 0 when the key is missing or expired and, like Redis,
+    // keeps the expiry of a live key.
+    fn incr(&self, key: &str) -> Result<i64, Box<dyn Error>> {
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries
+            .entry(key.to_string())
+            .or_insert_with(|| ("0".to_string(), None));
+        if is_expired(&entry.1) {
+            *entry = ("0".to_string(), None);
+        }
+        let n = entry.0.parse::<i64>()? + 1;
+        entry.0 = n.to_string();
+        Ok(n)
+    }
+
+    fn flush(&self, prefix: &str, keep: &[&str]) -> Result<u64, Box<dyn Error>> {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|k, _| !k.starts_with(prefix) || keep.contains(&k.as_str()));
+        Ok((before - entries.len()) as u64)
+    }
+
+    fn stats(&self) -> Vec<TierStats> {
+        vec![self.counter.snapshot("memory")]
+    }
+}
+
+fn is_expired(expire_at: &Option<Instant>) -> bool {
+    expire_at.is_some_and(|t| t <= Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incr_restarts_an_expired_counter() {
+        let cache = InMemoryCache::new();
+        cache.save("n", "41", Duration::ZERO).unwrap();
+        assert_eq!(cache.incr("n").unwrap(), 1);
+        assert_eq!(cache.incr("n").unwrap(), 2);
+    }
+
+    #[test]
+    fn load_drops_an_expired_entry() {
+        let cache = InMemoryCache::new();
+        cache.save("k", "v", Duration::ZERO).unwrap();
+        assert_eq!(cache.load("k").unwrap(), None);
+        assert!(cache.entries.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn save_sweeps_expired_entries_once_the_map_doubles() {
+        let cache = InMemoryCache::new();
+        for i in 0..MIN_SWEEP_SIZE {
+            cache
+                .save(&format!("old-{i}"), "v", Duration::ZERO)
+                .unwrap();
+        }
+        cache.save("new", "v", Duration::from_secs(60)).unwrap();
+        assert_eq!(cache.entries.read().unwrap().len(), 1);
+        assert_eq!(cache.load("new").unwrap().as_deref(), Some("v"));
+    }
+}
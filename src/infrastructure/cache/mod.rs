@@ -1,5 +1,17 @@
 mod redis;
-pub use redis::RedisCache;
+pub use redis::{RedisCache, RedisOptions};
+
+mod memory;
+pub use memory::InMemoryCache;
+
+mod fail_safe;
+pub use fail_safe::FailSafeCache;
+
+mod layered;
+pub use layered::LayeredCache;
+
+mod policy;
+pub use policy::Policy;
 
 mod helper;
-pub use helper::Helper;
+pub use helper::{Helper, HitCounter, TierStats};
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use rand::{thread_rng, Rng};
+
+// Policy decides what the operators cache, under which keys and for how long.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub enabled: bool,
+    // Prepended to every key, so deployments can share one Redis.
+    pub key_prefix: String,
+    pub book_list_ttl: Duration,
+    pub book_ttl: Duration,
+    pub reviews_ttl: Duration,
+    pub search_ttl: Duration,
+    // Fraction of a TTL added at random, so entries cached together do not
+    // all expire together.
+    pub jitter: f64,
+}
+
+impl Policy {
+    // jittered extends `ttl` by a random part of up to `jitter` of it.
+    pub fn jittered(&self, ttl: Duration) -> Duration {
+        if self.jitter <= 0.0 {
+            return ttl;
+        }
+        ttl.mul_f64(1.0 + thread_rng().gen_range(0.0..self.jitter))
+    }
+}
@@ -1,37 +1,149 @@
 use std::error::Error;
-use std::sync::RwLock;
+use std::time::Duration;
 
-use redis::{Client, Commands, Connection};
+use r2d2::{ManageConnection, NopErrorHandler, Pool};
+use redis::{Client, Commands, Connection, ConnectionLike, RedisError};
 
-use crate::infrastructure::cache::Helper;
+use crate::infrastructure::cache::{Helper, HitCounter, TierStats};
+use crate::infrastructure::health::Probe;
 
-const DEFAULT_TTL: u64 = 3600; // seconds
+// Keys removed per DEL command when flushing.
+const FLUSH_BATCH: usize = 500;
 
+// RedisOptions tunes the connection pool behind RedisCache.
+pub struct RedisOptions {
+    pub pool_size: u32,
+    pub connect_timeout: Duration,
+    pub io_timeout: Duration,
+}
+
+// RedisCache checks out a pooled connection per command, so concurrent
+// requests no longer queue on a single connection. A connection that fails
+// is dropped by the pool, and the pool keeps reopening connections in the
+// background, so Redis may be down at startup or restart at any time.
 pub struct RedisCache {
-    conn: RwLock<Connection>,
+    pool: Pool<RedisConnector>,
+    counter: HitCounter,
 }
 
 impl RedisCache {
-    pub fn new(redis_uri: &str) -> Result<Self, Box<dyn Error>> {
-        let client = Client::open(redis_uri)?;
-        let conn = client.get_connection()?;
+    pub fn new(redis_uri: &str, opts: RedisOptions) -> Result<Self, Box<dyn Error>> {
+        let connector = RedisConnector {
+            client: Client::open(redis_uri)?,
+            connect_timeout: opts.connect_timeout,
+            io_timeout: opts.io_timeout,
+        };
+        let pool = Pool::builder()
+            .max_size(opts.pool_size)
+            .connection_timeout(opts.connect_timeout)
+            // Broken connections are detected after use instead, which
+            // saves a PING round trip on every checkout.
+            .test_on_check_out(false)
+            // Outages are logged by FailSafeCache, not on every retry.
+            .error_handler(Box::new(NopErrorHandler))
+            .build_unchecked(connector);
         Ok(Self {
-            conn: RwLock::new(conn),
+            pool,
+            counter: HitCounter::default(),
         })
     }
 }
 
 impl Helper for RedisCache {
-    fn save(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
-        let mut conn = self.conn.write().unwrap();
-        conn.set_ex(key, value, DEFAULT_TTL)?;
+    fn save(&self, key: &str, value: &str, ttl: Duration) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.pool.get()?;
+        conn.set_ex::<_, _, ()>(key, value, ttl.as_secs().max(1))?;
         Ok(())
     }
 
     fn load(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
-        // Caution: `conn.read()` doesn't work here
-        let mut conn = self.conn.write().unwrap();
+        let mut conn = self.pool.get()?;
         let result: Option<String> = conn.get(key)?;
+        self.counter.record(&result);
+        Ok(result)
+    }
+
+    fn add(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, Box<dyn Error>> {
+        let mut conn = self.pool.get()?;
+        let result: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query(&mut *conn)?;
+        Ok(result.is_some())
+    }
+
+    fn incr(&self, key: &str) -> Result<i64, Box<dyn Error>> {
+        let mut conn = self.pool.get()?;
+        let result: i64 = conn.incr(key, 1)?;
         Ok(result)
     }
+
+    fn flush(&self, prefix: &str, keep: &[&str]) -> Result<u64, Box<dyn Error>> {
+        let mut conn = self.pool.get()?;
+        let keys: Vec<String> = conn
+            .scan_match::<_, String>(format!("{}*", glob_escape(prefix)))?
+            .filter(|k| !keep.contains(&k.as_str()))
+            .collect();
+        let mut removed = 0;
+        for batch in keys.chunks(FLUSH_BATCH) {
+            removed += conn.del::<_, u64>(batch)?;
+        }
+        Ok(removed)
+    }
+
+    fn stats(&self) -> Vec<TierStats> {
+        vec![self.counter.snapshot("redis")]
+    }
+}
+
+impl Probe for RedisCache {
+    fn ping(&self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.pool.get_timeout(timeout)?;
+        redis::cmd("PING").query::<()>(&mut *conn)?;
+        Ok(())
+    }
+}
+
+// RedisConnector opens connections with the configured timeouts.
+struct RedisConnector {
+    client: Client,
+    connect_timeout: Duration,
+    io_timeout: Duration,
+}
+
+impl ManageConnection for RedisConnector {
+    type Connection = Connection;
+    type Error = RedisError;
+
+    fn connect(&self) -> Result<Connection, RedisError> {
+        let conn = self
+            .client
+            .get_connection_with_timeout(self.connect_timeout)?;
+        conn.set_read_timeout(Some(self.io_timeout))?;
+        conn.set_write_timeout(Some(self.io_timeout))?;
+        Ok(conn)
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> Result<(), RedisError> {
+        redis::cmd("PING").query(conn)
+    }
+
+    fn has_broken(&self, conn: &mut Connection) -> bool {
+        !conn.is_open()
+    }
+}
+
+// glob_escape quotes the characters SCAN MATCH treats as wildcards.
+fn glob_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
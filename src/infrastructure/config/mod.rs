@@ -1,40 +1,433 @@
-use std::{fs::File, io::Read};
+use std::fmt;
+use std::path::Path;
 
+use figment::providers::{Env, Format, Serialized, Toml};
+use figment::Figment;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+// Config file read when neither `--config` nor LRBOOKS_CONFIG names one.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+// Environment variables starting with this prefix override config keys,
+// e.g. LRBOOKS__APP__PORT overrides `app.port`.
+const ENV_PREFIX: &str = "LRBOOKS__";
+// The token secret shipped in the sample config files.
+const SAMPLE_SECRET: &str = "I_Love_LiteRank";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
     pub app: ApplicationConfig,
     pub cache: CacheConfig,
     pub db: DBConfig,
+    pub backend: BackendConfig,
+    pub password: PasswordConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct DBConfig {
     pub file_name: String,
     pub dsn: String,
+    // File holding `dsn`, so the password can stay out of the config file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dsn_file: Option<String>,
     pub mongo_uri: String,
+    // File holding `mongo_uri`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mongo_uri_file: Option<String>,
     pub mongo_db_name: String,
+    // Apply pending migrations at startup. SQLite files are always migrated.
+    pub auto_migrate: bool,
+}
+
+impl Default for DBConfig {
+    fn default() -> Self {
+        DBConfig {
+            file_name: "test.db".to_string(),
+            dsn: String::new(),
+            dsn_file: None,
+            mongo_uri: String::new(),
+            mongo_uri_file: None,
+            mongo_db_name: "lr_book".to_string(),
+            auto_migrate: false,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct CacheConfig {
+    // Cache books and reviews read through the operators.
+    pub enabled: bool,
+    // Prepended to every cache key, so deployments can share one Redis.
+    pub key_prefix: String,
+    // Seconds a page of the unfiltered book list is cached.
+    pub book_list_ttl: u64,
+    // Seconds a single book is cached.
+    pub book_ttl: u64,
+    // Seconds the unfiltered review list of a book is cached.
+    pub reviews_ttl: u64,
+    // Seconds the results of a book or review search are cached.
+    pub search_ttl: u64,
+    // Fraction of a TTL added at random to spread out expirations.
+    pub ttl_jitter: f64,
+    // Book list pages loaded into the cache at launch; 0 skips the warm-up.
+    pub warm_up_pages: u32,
     pub redis_uri: String,
+    // File holding `redis_uri`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis_uri_file: Option<String>,
+    // Entries kept in the in-process LRU in front of Redis; 0 disables it.
+    pub local_size: usize,
+    // Seconds an entry may be served from the in-process LRU.
+    pub local_ttl: u64,
+    // Maximum number of pooled Redis connections.
+    pub pool_size: u32,
+    // Milliseconds to wait for a Redis connection to open or be checked out.
+    pub connect_timeout_ms: u64,
+    // Milliseconds to wait on a single Redis read or write.
+    pub io_timeout_ms: u64,
+    // Consecutive Redis failures that open the circuit breaker.
+    pub breaker_threshold: u32,
+    // Milliseconds Redis is bypassed once the circuit breaker opens.
+    pub breaker_cooldown_ms: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            key_prefix: "lr-".to_string(),
+            book_list_ttl: 3600,
+            book_ttl: 3600,
+            reviews_ttl: 3600,
+            search_ttl: 600,
+            ttl_jitter: 0.1,
+            warm_up_pages: 0,
+            redis_uri: String::new(),
+            redis_uri_file: None,
+            local_size: 1000,
+            local_ttl: 10,
+            pool_size: 16,
+            connect_timeout_ms: 1000,
+            io_timeout_ms: 500,
+            breaker_threshold: 5,
+            breaker_cooldown_ms: 5000,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct ApplicationConfig {
     pub address: String,
-    pub port: i32,
+    pub port: u16,
     pub page_size: u32,
     pub token_secret: String,
+    // File holding `token_secret`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_secret_file: Option<String>,
+    // Lifetime of refresh tokens.
     pub token_hours: u32,
+    // Lifetime of access tokens.
+    pub access_token_minutes: u32,
+    // Milliseconds the readiness probe waits for each backend to answer.
+    pub health_timeout_ms: u64,
+    // Accepts access tokens unchecked while revocations cannot be read,
+    // instead of failing requests with 503.
+    pub auth_fail_open: bool,
+    // Allows local-only settings such as the sample token secret.
+    pub dev_mode: bool,
+}
+
+impl Default for ApplicationConfig {
+    fn default() -> Self {
+        ApplicationConfig {
+            address: "127.0.0.1".to_string(),
+            port: 8000,
+            page_size: 5,
+            token_secret: String::new(),
+            token_secret_file: None,
+            token_hours: 48,
+            access_token_minutes: 15,
+            health_timeout_ms: 1000,
+            auth_fail_open: false,
+            dev_mode: false,
+        }
+    }
+}
+
+// PasswordConfig sets the Argon2id cost of new password hashes. Stored
+// hashes made with another cost are upgraded when their owners sign in.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PasswordConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        PasswordConfig {
+            memory_kib: 19456,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+// BackendConfig picks an implementation for each gateway and the cache.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackendConfig {
+    pub book: DatabaseBackend,
+    pub user: DatabaseBackend,
+    pub review: ReviewBackend,
+    pub cache: CacheBackend,
 }
 
-pub fn parse_config(file_name: &str) -> Config {
-    let mut file = File::open(file_name).expect("Failed to open TOML config file");
-    let mut content = String::new();
-    file.read_to_string(&mut content)
-        .expect("Failed to read TOML config file");
-    toml::from_str(&content).expect("Failed to parse TOML config file")
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    #[default]
+    MySQL,
+    SQLite,
+    Memory,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewBackend {
+    #[default]
+    Mongo,
+    MySQL,
+    SQLite,
+    Memory,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    #[default]
+    Redis,
+    Memory,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    // Load covers unreadable files and values of the wrong type.
+    Load(String),
+    // Invalid lists every key whose value failed validation.
+    Invalid(Vec<InvalidKey>),
+}
+
+#[derive(Debug)]
+pub struct InvalidKey {
+    pub key: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Load(msg) => write!(f, "{msg}"),
+            ConfigError::Invalid(keys) => {
+                let msgs: Vec<String> = keys
+                    .iter()
+                    .map(|k| format!("`{}` {}", k.key, k.message))
+                    .collect();
+                write!(f, "{}", msgs.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// parse_config layers built-in defaults, the config file, secret files and
+// LRBOOKS__ environment variables, then validates the result. Without an
+// explicit `file_name` a missing DEFAULT_CONFIG_FILE is not an error.
+pub fn parse_config(file_name: Option<&str>) -> Result<Config, ConfigError> {
+    if let Some(f) = file_name {
+        if !Path::new(f).is_file() {
+            return Err(ConfigError::Load(format!("config file {f} not found")));
+        }
+    }
+    let mut c: Config = Figment::from(Serialized::defaults(Config::default()))
+        .merge(Toml::file(file_name.unwrap_or(DEFAULT_CONFIG_FILE)))
+        .merge(Env::prefixed(ENV_PREFIX).split("__"))
+        .extract()
+        .map_err(load_error)?;
+    read_secret(
+        "app.token_secret_file",
+        &c.app.token_secret_file,
+        &mut c.app.token_secret,
+    )?;
+    read_secret("db.dsn_file", &c.db.dsn_file, &mut c.db.dsn)?;
+    read_secret(
+        "db.mongo_uri_file",
+        &c.db.mongo_uri_file,
+        &mut c.db.mongo_uri,
+    )?;
+    read_secret(
+        "cache.redis_uri_file",
+        &c.cache.redis_uri_file,
+        &mut c.cache.redis_uri,
+    )?;
+    validate(&c)?;
+    Ok(c)
+}
+
+// load_error names the key of every value that could not be loaded.
+fn load_error(err: figment::Error) -> ConfigError {
+    let msgs: Vec<String> = err
+        .into_iter()
+        .map(|e| {
+            let source = match &e.metadata {
+                Some(m) => format!(" in {}", m.name),
+                None => String::new(),
+            };
+            if e.path.is_empty() {
+                format!("{}{}", e.kind, source)
+            } else {
+                let key = e.path.join(".").to_lowercase();
+                format!("`{}` {}{}", key, e.kind, source)
+            }
+        })
+        .collect();
+    ConfigError::Load(msgs.join("; "))
+}
+
+// read_secret replaces `value` with the contents of the file at `path`.
+fn read_secret(
+    key: &'static str,
+    path: &Option<String>,
+    value: &mut String,
+) -> Result<(), ConfigError> {
+    if let Some(path) = path {
+        let secret = std::fs::read_to_string(path).map_err(|e| {
+            ConfigError::Invalid(vec![InvalidKey {
+                key,
+                message: format!("cannot read {path}: {e}"),
+            }])
+        })?;
+        *value = secret.trim_end_matches(['\r', '\n']).to_string();
+    }
+    Ok(())
+}
+
+// validate reports every invalid key at once.
+fn validate(c: &Config) -> Result<(), ConfigError> {
+    let mut errs = Vec::new();
+    let mut check = |ok: bool, key: &'static str, message: &str| {
+        if !ok {
+            errs.push(InvalidKey {
+                key,
+                message: message.to_string(),
+            });
+        }
+    };
+    let (app, db, cache, b, pw) = (&c.app, &c.db, &c.cache, &c.backend, &c.password);
+
+    check(app.page_size > 0, "app.page_size", "must be greater than 0");
+    check(
+        app.token_hours > 0,
+        "app.token_hours",
+        "must be greater than 0",
+    );
+    check(
+        app.access_token_minutes > 0,
+        "app.access_token_minutes",
+        "must be greater than 0",
+    );
+    check(
+        app.health_timeout_ms > 0,
+        "app.health_timeout_ms",
+        "must be greater than 0",
+    );
+    check(
+        !app.token_secret.is_empty(),
+        "app.token_secret",
+        "must be set",
+    );
+    check(
+        app.dev_mode || app.token_secret != SAMPLE_SECRET,
+        "app.token_secret",
+        "is the sample secret; set your own or enable app.dev_mode",
+    );
+
+    let uses = |d: DatabaseBackend| b.book == d || b.user == d;
+    if uses(DatabaseBackend::MySQL) || b.review == ReviewBackend::MySQL {
+        check(!db.dsn.is_empty(), "db.dsn", "must be set to use MySQL");
+    }
+    if uses(DatabaseBackend::SQLite) || b.review == ReviewBackend::SQLite {
+        check(
+            !db.file_name.is_empty(),
+            "db.file_name",
+            "must be set to use SQLite",
+        );
+    }
+    if b.review == ReviewBackend::Mongo {
+        check(
+            !db.mongo_uri.is_empty(),
+            "db.mongo_uri",
+            "must be set to use MongoDB",
+        );
+        check(
+            !db.mongo_db_name.is_empty(),
+            "db.mongo_db_name",
+            "must be set to use MongoDB",
+        );
+    }
+
+    let ttl_msg = "must be greater than 0; set cache.enabled = false to disable caching";
+    check(cache.book_list_ttl > 0, "cache.book_list_ttl", ttl_msg);
+    check(cache.book_ttl > 0, "cache.book_ttl", ttl_msg);
+    check(cache.reviews_ttl > 0, "cache.reviews_ttl", ttl_msg);
+    check(cache.search_ttl > 0, "cache.search_ttl", ttl_msg);
+    check(
+        (0.0..=1.0).contains(&cache.ttl_jitter),
+        "cache.ttl_jitter",
+        "must be between 0 and 1",
+    );
+    if b.cache == CacheBackend::Redis {
+        check(
+            !cache.redis_uri.is_empty(),
+            "cache.redis_uri",
+            "must be set to use Redis",
+        );
+        check(
+            cache.pool_size > 0,
+            "cache.pool_size",
+            "must be greater than 0",
+        );
+        check(
+            cache.breaker_threshold > 0,
+            "cache.breaker_threshold",
+            "must be greater than 0",
+        );
+    }
+
+    check(
+        pw.iterations > 0,
+        "password.iterations",
+        "must be greater than 0",
+    );
+    check(
+        (1..=0xFFFFFF).contains(&pw.parallelism),
+        "password.parallelism",
+        "must be between 1 and 16777215",
+    );
+    check(
+        pw.memory_kib >= 8 * pw.parallelism,
+        "password.memory_kib",
+        "must be at least 8 times password.parallelism",
+    );
+
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(errs))
+    }
 }
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::RwLock;
+
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+
+use super::mongo::parse_object_id;
+
+use crate::domain::gateway::{BookManager, ReviewManager, UserManager};
+use crate::domain::{model, DomainError};
+
+// InMemoryBookManager keeps books in process, ordered by id like a table scan.
+pub struct InMemoryBookManager {
+    books: RwLock<BTreeMap<u32, model::Book>>,
+    last_id: AtomicU32,
+    page_size: u32,
+}
+
+impl InMemoryBookManager {
+    pub fn new(page_size: u32) -> Self {
+        InMemoryBookManager {
+            books: RwLock::new(BTreeMap::new()),
+            last_id: AtomicU32::new(0),
+            page_size,
+        }
+    }
+}
+
+impl BookManager for InMemoryBookManager {
+    fn create_book(&self, b: &model::Book) -> Result<u32, DomainError> {
+        let mut books = self.books.write().unwrap();
+        let id = self.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let now = now_string();
+        books.insert(
+            id,
+            model::Book {
+                id,
+                created_at: now.clone(),
+                updated_at: now,
+                ..b.clone()
+            },
+        );
+        Ok(id)
+    }
+
+    fn update_book(&self, id: u32, b: &model::Book) -> Result<bool, DomainError> {
+        let mut books = self.books.write().unwrap();
+        let Some(book) = books.get_mut(&id) else {
+            return Ok(false);
+        };
+        *book = model::Book {
+            id,
+            created_at: book.created_at.clone(),
+            updated_at: now_string(),
+            ..b.clone()
+        };
+        Ok(true)
+    }
+
+    fn patch_book(&self, id: u32, p: &model::BookPatch) -> Result<bool, DomainError> {
+        let mut books = self.books.write().unwrap();
+        let Some(book) = books.get_mut(&id) else {
+            return Ok(false);
+        };
+        let p = p.clone();
+        if let Some(v) = p.title {
+            book.title = v;
+        }
+        if let Some(v) = p.author {
+            book.author = v;
+        }
+        if let Some(v) = p.published_at {
+            book.published_at = v;
+        }
+        if let Some(v) = p.description {
+            book.description = v;
+        }
+        if let Some(v) = p.isbn {
+            book.isbn = v;
+        }
+        if let Some(v) = p.total_pages {
+            book.total_pages = v;
+        }
+        book.updated_at = now_string();
+        Ok(true)
+    }
+
+    fn delete_book(&self, id: u32) -> Result<bool, DomainError> {
+        Ok(self.books.write().unwrap().remove(&id).is_some())
+    }
+
+    fn get_book(&self, id: u32) -> Result<Option<model::Book>, DomainError> {
+        Ok(self.books.read().unwrap().get(&id).cloned())
+    }
+
+    fn get_books(&self, offset: u32, keyword: &str) -> Result<Vec<model::Book>, DomainError> {
+        let books = self.books.read().unwrap();
+        Ok(books
+            .values()
+            .filter(|b| {
+                keyword.is_empty() || contains(&b.title, keyword) || contains(&b.author, keyword)
+            })
+            .skip(offset as usize)
+            .take(self.page_size as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+// InMemoryUserManager keeps users in process, with emails kept unique.
+pub struct InMemoryUserManager {
+    users: RwLock<BTreeMap<u32, model::User>>,
+    last_id: AtomicU32,
+    page_size: u32,
+}
+
+impl InMemoryUserManager {
+    pub fn new(page_size: u32) -> Self {
+        InMemoryUserManager {
+            users: RwLock::new(BTreeMap::new()),
+            last_id: AtomicU32::new(0),
+            page_size,
+        }
+    }
+}
+
+impl UserManager for InMemoryUserManager {
+    fn create_user(&self, u: &model::User) -> Result<u32, DomainError> {
+        let mut users = self.users.write().unwrap();
+        if users.values().any(|x| x.email == u.email) {
+            return Err(DomainError::Conflict(format!(
+                "duplicate email {}",
+                u.email
+            )));
+        }
+        let id = self.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+        users.insert(id, model::User { id, ..u.clone() });
+        Ok(id)
+    }
+
+    fn get_user(&self, id: u32) -> Result<Option<model::User>, DomainError> {
+        Ok(self.users.read().unwrap().get(&id).cloned())
+    }
+
+    fn get_user_by_email(&self, email: &str) -> Result<Option<model::User>, DomainError> {
+        let users = self.users.read().unwrap();
+        Ok(users.values().find(|u| u.email == email).cloned())
+    }
+
+    fn get_users(&self, offset: u32) -> Result<Vec<model::User>, DomainError> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .values()
+            .skip(offset as usize)
+            .take(self.page_size as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn update_role(&self, id: u32, role: &model::UserPermission) -> Result<bool, DomainError> {
+        let mut users = self.users.write().unwrap();
+        let Some(u) = users.get_mut(&id) else {
+            return Ok(false);
+        };
+        u.role = role.clone();
+        u.updated_at = now_string();
+        Ok(true)
+    }
+
+    fn update_password(&self, id: u32, password: &str, salt: &str) -> Result<(), DomainError> {
+        if let Some(u) = self.users.write().unwrap().get_mut(&id) {
+            u.password = password.to_string();
+            u.salt = salt.to_string();
+        }
+        Ok(())
+    }
+}
+
+// InMemoryReviewManager keeps reviews in process, keyed by ObjectId hex
+// strings just like the MongoDB collection.
+#[derive(Default)]
+pub struct InMemoryReviewManager {
+    reviews: RwLock<BTreeMap<String, model::Review>>,
+}
+
+impl InMemoryReviewManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ReviewManager for InMemoryReviewManager {
+    fn create_review(&self, r: &model::Review) -> Result<String, DomainError> {
+        let id = ObjectId::new().to_hex();
+        self.reviews.write().unwrap().insert(
+            id.clone(),
+            model::Review {
+                id: id.clone(),
+                ..r.clone()
+            },
+        );
+        Ok(id)
+    }
+
+    fn update_review(&self, id: &str, r: &model::Review) -> Result<bool, DomainError> {
+        parse_object_id(id)?;
+        let mut reviews = self.reviews.write().unwrap();
+        let Some(review) = reviews.get_mut(id) else {
+            return Ok(false);
+        };
+        review.title = r.title.clone();
+        review.content = r.content.clone();
+        review.updated_at = Utc::now();
+        Ok(true)
+    }
+
+    fn patch_review(&self, id: &str, p: &model::ReviewPatch) -> Result<bool, DomainError> {
+        parse_object_id(id)?;
+        let mut reviews = self.reviews.write().unwrap();
+        let Some(review) = reviews.get_mut(id) else {
+            return Ok(false);
+        };
+        if let Some(title) = &p.title {
+            review.title = title.clone();
+        }
+        if let Some(content) = &p.content {
+            review.content = content.clone();
+        }
+        review.updated_at = Utc::now();
+        Ok(true)
+    }
+
+    fn delete_review(&self, id: &str) -> Result<bool, DomainError> {
+        parse_object_id(id)?;
+        Ok(self.reviews.write().unwrap().remove(id).is_some())
+    }
+
+    fn get_review(&self, id: &str) -> Result<Option<model::Review>, DomainError> {
+        parse_object_id(id)?;
+        Ok(self.reviews.read().unwrap().get(id).cloned())
+    }
+
+    fn get_reviews_of_book(
+        &self,
+        book_id: u32,
+        keyword: &str,
+    ) -> Result<Vec<model::Review>, DomainError> {
+        let reviews = self.reviews.read().unwrap();
+        Ok(reviews
+            .values()
+            .filter(|r| r.book_id == book_id)
+            .filter(|r| {
+                keyword.is_empty() || contains(&r.title, keyword) || contains(&r.content, keyword)
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+fn now_string() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// contains matches case-insensitively, like LIKE on the MySQL default collation.
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
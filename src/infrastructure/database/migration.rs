@@ -0,0 +1,103 @@
+use std::error::Error;
+
+// Migration is one versioned schema change together with its rollback.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($dialect:literal, $version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/migrations/",
+                $dialect,
+                "/",
+                $name,
+                ".up.sql"
+            )),
+            down: include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/migrations/",
+                $dialect,
+                "/",
+                $name,
+                ".down.sql"
+            )),
+        }
+    };
+}
+
+pub const MYSQL_MIGRATIONS: &[Migration] = &[
+    migration!("mysql", 1, "0001_create_books"),
+    migration!("mysql", 2, "0002_create_users"),
+    migration!("mysql", 3, "0003_create_reviews"),
+    migration!("mysql", 4, "0004_add_user_role"),
+    migration!("mysql", 5, "0005_add_review_owner"),
+];
+
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    migration!("sqlite", 1, "0001_create_books"),
+    migration!("sqlite", 2, "0002_create_users"),
+    migration!("sqlite", 3, "0003_create_reviews"),
+    migration!("sqlite", 4, "0004_add_user_role"),
+    migration!("sqlite", 5, "0005_add_review_owner"),
+];
+
+// Table that records which migrations have been applied.
+pub const HISTORY_TABLE: &str = "schema_migrations";
+
+// Migrator is implemented by every relational persistence.
+pub trait Migrator: Send + Sync {
+    fn migrations(&self) -> &'static [Migration];
+    fn applied_versions(&self) -> Result<Vec<u32>, Box<dyn Error>>;
+    fn apply(&self, m: &Migration) -> Result<(), Box<dyn Error>>;
+    fn revert(&self, m: &Migration) -> Result<(), Box<dyn Error>>;
+}
+
+// migrate_up applies every pending migration in version order.
+pub fn migrate_up(m: &dyn Migrator) -> Result<Vec<&'static Migration>, Box<dyn Error>> {
+    let applied = m.applied_versions()?;
+    let mut done = Vec::new();
+    for mig in m.migrations() {
+        if !applied.contains(&mig.version) {
+            m.apply(mig)?;
+            done.push(mig);
+        }
+    }
+    Ok(done)
+}
+
+// migrate_down reverts the latest `steps` applied migrations.
+pub fn migrate_down(
+    m: &dyn Migrator,
+    steps: usize,
+) -> Result<Vec<&'static Migration>, Box<dyn Error>> {
+    let applied = m.applied_versions()?;
+    let mut done = Vec::new();
+    for mig in m
+        .migrations()
+        .iter()
+        .rev()
+        .filter(|mig| applied.contains(&mig.version))
+        .take(steps)
+    {
+        m.revert(mig)?;
+        done.push(mig);
+    }
+    Ok(done)
+}
+
+// status lists every known migration and whether it is applied.
+pub fn status(m: &dyn Migrator) -> Result<Vec<(&'static Migration, bool)>, Box<dyn Error>> {
+    let applied = m.applied_versions()?;
+    Ok(m.migrations()
+        .iter()
+        .map(|mig| (mig, applied.contains(&mig.version)))
+        .collect())
+}
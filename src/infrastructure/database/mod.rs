@@ -1,5 +1,16 @@
+mod sql;
+
 mod mysql;
 pub use mysql::MySQLPersistence;
 
 mod mongo;
 pub use mongo::MongoPersistence;
+
+mod sqlite;
+pub use sqlite::SQLitePersistence;
+
+mod memory;
+pub use memory::{InMemoryBookManager, InMemoryReviewManager, InMemoryUserManager};
+
+pub mod migration;
+pub use migration::Migrator;
@@ -1,31 +1,47 @@
 use std::error::Error;
+use std::time::Duration;
 
 use mongodb::{
     bson::{doc, oid::ObjectId, DateTime, Regex},
     error::Error as MongoError,
-    sync::{Client, Collection},
+    sync::{Client, Collection, Database},
 };
 
 use crate::domain::gateway::ReviewManager;
-use crate::domain::model::Review;
+use crate::domain::model::{Review, ReviewPatch};
+use crate::domain::DomainError;
+use crate::infrastructure::health::Probe;
 
 const COLL_REVIEW: &str = "reviews";
 const ID_FIELD: &str = "_id";
 
 pub struct MongoPersistence {
+    db: Database,
     coll: Collection<Review>,
 }
 
 impl MongoPersistence {
     pub fn new(mongo_uri: &str, db_name: &str) -> Result<Self, MongoError> {
         let client = Client::with_uri_str(mongo_uri)?;
-        let coll = client.database(db_name).collection::<Review>(COLL_REVIEW);
-        Ok(Self { coll })
+        let db = client.database(db_name);
+        let coll = db.collection::<Review>(COLL_REVIEW);
+        Ok(Self { db, coll })
     }
 }
 
+impl From<MongoError> for DomainError {
+    fn from(err: MongoError) -> Self {
+        DomainError::Backend(err.to_string())
+    }
+}
+
+// parse_object_id rejects ids that are not 24-digit hex ObjectIds.
+pub fn parse_object_id(id: &str) -> Result<ObjectId, DomainError> {
+    ObjectId::parse_str(id).map_err(|_| DomainError::Validation(format!("invalid review id {id}")))
+}
+
 impl ReviewManager for MongoPersistence {
-    fn create_review(&self, review: &Review) -> Result<String, Box<dyn Error>> {
+    fn create_review(&self, review: &Review) -> Result<String, DomainError> {
         let result = self.coll.insert_one(review.clone(), None)?;
         let inserted_id = result
             .inserted_id
@@ -34,28 +50,44 @@ impl ReviewManager for MongoPersistence {
         Ok(inserted_id.to_hex())
     }
 
-    fn update_review(&self, id: &str, review: &Review) -> Result<(), Box<dyn Error>> {
-        let object_id = ObjectId::parse_str(id)?;
+    fn update_review(&self, id: &str, review: &Review) -> Result<bool, DomainError> {
+        let object_id = parse_object_id(id)?;
         let update_values = doc! {
             "title": &review.title,
             "content": &review.content,
             "updated_at": DateTime::now(),
         };
         let filter = doc! { ID_FIELD: object_id };
-        let _result = self
+        let result = self
             .coll
             .update_one(filter, doc! { "$set": update_values }, None)?;
-        Ok(())
+        Ok(result.matched_count > 0)
     }
 
-    fn delete_review(&self, id: &str) -> Result<(), Box<dyn Error>> {
-        let object_id = ObjectId::parse_str(id)?;
-        self.coll.delete_one(doc! { ID_FIELD: object_id }, None)?;
-        Ok(())
+    fn patch_review(&self, id: &str, p: &ReviewPatch) -> Result<bool, DomainError> {
+        let object_id = parse_object_id(id)?;
+        let mut update_values = doc! { "updated_at": DateTime::now() };
+        if let Some(title) = &p.title {
+            update_values.insert("title", title);
+        }
+        if let Some(content) = &p.content {
+            update_values.insert("content", content);
+        }
+        let filter = doc! { ID_FIELD: object_id };
+        let result = self
+            .coll
+            .update_one(filter, doc! { "$set": update_values }, None)?;
+        Ok(result.matched_count > 0)
+    }
+
+    fn delete_review(&self, id: &str) -> Result<bool, DomainError> {
+        let object_id = parse_object_id(id)?;
+        let result = self.coll.delete_one(doc! { ID_FIELD: object_id }, None)?;
+        Ok(result.deleted_count > 0)
     }
 
-    fn get_review(&self, id: &str) -> Result<Option<Review>, Box<dyn Error>> {
-        let object_id = ObjectId::parse_str(id)?;
+    fn get_review(&self, id: &str) -> Result<Option<Review>, DomainError> {
+        let object_id = parse_object_id(id)?;
         let filter = doc! { ID_FIELD: object_id };
         let review = self.coll.find_one(filter, None)?;
         if let Some(review_doc) = review {
@@ -68,11 +100,7 @@ impl ReviewManager for MongoPersistence {
         }
     }
 
-    fn get_reviews_of_book(
-        &self,
-        book_id: u32,
-        keyword: &str,
-    ) -> Result<Vec<Review>, Box<dyn Error>> {
+    fn get_reviews_of_book(&self, book_id: u32, keyword: &str) -> Result<Vec<Review>, DomainError> {
         let mut filter = doc! { "book_id": book_id };
         if !keyword.is_empty() {
             filter = doc! {
@@ -95,3 +123,12 @@ impl ReviewManager for MongoPersistence {
         Ok(reviews)
     }
 }
+
+impl Probe for MongoPersistence {
+    // ping cannot be cut short by `timeout`: the driver waits for a server
+    // as long as its server selection timeout.
+    fn ping(&self, _timeout: Duration) -> Result<(), Box<dyn Error>> {
+        self.db.run_command(doc! {"ping": 1}, None)?;
+        Ok(())
+    }
+}
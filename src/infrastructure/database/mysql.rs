@@ -1,11 +1,20 @@
 use std::error::Error;
+use std::time::Duration;
 
-use chrono::Utc;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use mysql::prelude::Queryable;
-use mysql::{Error as MySQLError, Pool};
+use mysql::{Error as MySQLError, Params, Pool, PooledConn, Value};
 
-use crate::domain::gateway::{BookManager, UserManager};
-use crate::domain::model;
+use crate::domain::gateway::{BookManager, ReviewManager, UserManager};
+use crate::domain::{model, DomainError};
+use crate::infrastructure::database::migration::{
+    Migration, Migrator, HISTORY_TABLE, MYSQL_MIGRATIONS,
+};
+use crate::infrastructure::database::sql::{
+    book_patch_columns, like_pattern, parse_review_id, review_patch_columns, set_clause,
+    PatchValue, LIKE_ESCAPE,
+};
+use crate::infrastructure::health::Probe;
 
 pub struct MySQLPersistence {
     pool: Pool,
@@ -19,8 +28,20 @@ impl MySQLPersistence {
     }
 }
 
+// MySQL error number for a duplicate key.
+const ER_DUP_ENTRY: u16 = 1062;
+
+impl From<MySQLError> for DomainError {
+    fn from(err: MySQLError) -> Self {
+        match err {
+            MySQLError::MySqlError(e) if e.code == ER_DUP_ENTRY => DomainError::Conflict(e.message),
+            err => DomainError::Backend(err.to_string()),
+        }
+    }
+}
+
 impl BookManager for MySQLPersistence {
-    fn create_book(&self, b: &model::Book) -> Result<u32, Box<dyn Error>> {
+    fn create_book(&self, b: &model::Book) -> Result<u32, DomainError> {
         let mut conn = self.pool.get_conn()?;
         conn.exec::<usize, &str, (String, String, String, String, String, u32)>(
             "INSERT INTO books (title, author, published_at, description, isbn, total_pages)
@@ -37,126 +58,77 @@ impl BookManager for MySQLPersistence {
         Ok(conn.last_insert_id() as u32)
     }
 
-    fn update_book(&self, id: u32, b: &model::Book) -> Result<(), Box<dyn Error>> {
+    fn update_book(&self, id: u32, b: &model::Book) -> Result<bool, DomainError> {
         let mut conn = self.pool.get_conn()?;
         conn.exec::<usize, &str, (String, String, String, String, String, u32, String, u32)>(
             "UPDATE books SET title = ?, author = ?, published_at = ?, description = ?, isbn = ?, total_pages = ?, updated_at = ?
             WHERE id = ?",
-            (b.title.clone(), b.author.clone(), b.published_at.clone(), b.description.clone(), b.isbn.clone(), b.total_pages, Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),  id),
+            (b.title.clone(), b.author.clone(), b.published_at.clone(), b.description.clone(), b.isbn.clone(), b.total_pages, Utc::now().format(DATETIME_FORMAT).to_string(),  id),
         )?;
-        Ok(())
+        Ok(rows_matched(&conn) > 0)
     }
 
-    fn delete_book(&self, id: u32) -> Result<(), Box<dyn Error>> {
+    fn patch_book(&self, id: u32, p: &model::BookPatch) -> Result<bool, DomainError> {
+        let columns = book_patch_columns(p);
+        let mut conn = self.pool.get_conn()?;
+        conn.exec_drop(
+            format!("UPDATE books SET {} WHERE id = ?", set_clause(&columns)),
+            patch_params(columns, Utc::now().format(DATETIME_FORMAT).to_string(), id),
+        )?;
+        Ok(rows_matched(&conn) > 0)
+    }
+
+    fn delete_book(&self, id: u32) -> Result<bool, DomainError> {
         let mut conn = self.pool.get_conn()?;
         conn.exec::<usize, &str, (u32,)>("DELETE FROM books WHERE id = ?", (id,))?;
-        Ok(())
+        Ok(conn.affected_rows() > 0)
     }
 
-    fn get_book(&self, id: u32) -> Result<Option<model::Book>, Box<dyn Error>> {
+    fn get_book(&self, id: u32) -> Result<Option<model::Book>, DomainError> {
         let mut conn = self.pool.get_conn()?;
-        let books = conn.query_map(
-            format!("SELECT * FROM books WHERE ID = {}", id),
-            |(
-                id,
-                title,
-                author,
-                published_at,
-                description,
-                isbn,
-                total_pages,
-                created_at,
-                updated_at,
-            ): (
-                u64,
-                String,
-                String,
-                String,
-                String,
-                String,
-                u64,
-                String,
-                String,
-            )| {
-                model::Book {
-                    id: id as u32,
-                    title,
-                    author,
-                    published_at,
-                    description,
-                    isbn,
-                    total_pages: total_pages as u32,
-                    created_at,
-                    updated_at,
-                }
-            },
-        )?;
-        Ok(books.first().cloned())
-    }
-
-    fn get_books(&self, offset: u32, keyword: &str) -> Result<Vec<model::Book>, Box<dyn Error>> {
-        let mut conn = self.pool.get_conn()?;
-        let mut query = format!("SELECT * FROM books LIMIT {}, {}", offset, self.page_size);
-        if !keyword.is_empty() {
-            // Warning: don't do SQL concatenation in production code. It leads to SQL Injection Vulnerabilities.
-            // Here is for tutorial brevity.
-            let term = format!("%{}%", keyword.replace('\'', ""));
-            query = format!(
-                "SELECT * FROM books WHERE title LIKE '{}' OR author LIKE '{}' LIMIT {}, {}",
-                term, term, offset, self.page_size
-            );
-        }
-        let books = conn.query_map(
-            query,
-            |(
-                id,
-                title,
-                author,
-                published_at,
-                description,
-                isbn,
-                total_pages,
-                created_at,
-                updated_at,
-            ): (
-                u64,
-                String,
-                String,
-                String,
-                String,
-                String,
-                u64,
-                String,
-                String,
-            )| {
-                model::Book {
-                    id: id as u32,
-                    title,
-                    author,
-                    published_at,
-                    description,
-                    isbn,
-                    total_pages: total_pages as u32,
-                    created_at,
-                    updated_at,
-                }
-            },
+        let books = conn.exec_map(
+            format!("SELECT {BOOK_COLUMNS} FROM books WHERE id = ?"),
+            (id,),
+            book_from_row,
         )?;
+        Ok(books.into_iter().next())
+    }
+
+    fn get_books(&self, offset: u32, keyword: &str) -> Result<Vec<model::Book>, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        let books = if keyword.is_empty() {
+            conn.exec_map(
+                format!("SELECT {BOOK_COLUMNS} FROM books LIMIT ?, ?"),
+                (offset, self.page_size),
+                book_from_row,
+            )?
+        } else {
+            let term = like_pattern(keyword);
+            conn.exec_map(
+                format!(
+                    "SELECT {BOOK_COLUMNS} FROM books
+                     WHERE title LIKE ? ESCAPE '{LIKE_ESCAPE}' OR author LIKE ? ESCAPE '{LIKE_ESCAPE}'
+                     LIMIT ?, ?"
+                ),
+                (term.clone(), term, offset, self.page_size),
+                book_from_row,
+            )?
+        };
         Ok(books)
     }
 }
 
 impl UserManager for MySQLPersistence {
-    fn create_user(&self, u: &model::User) -> Result<u32, Box<dyn Error>> {
+    fn create_user(&self, u: &model::User) -> Result<u32, DomainError> {
         let mut conn = self.pool.get_conn()?;
-        conn.exec::<usize, &str, (String, String, String, bool, String, String)>(
-            "INSERT INTO users (email, password, salt, is_admin, created_at, updated_at)
+        conn.exec::<usize, &str, (String, String, String, &str, String, String)>(
+            "INSERT INTO users (email, password, salt, role, created_at, updated_at)
              VALUES (?, ?, ?, ?, ?, ?)",
             (
                 u.email.clone(),
                 u.password.clone(),
                 u.salt.clone(),
-                u.is_admin,
+                u.role.as_str(),
                 u.created_at.clone(),
                 u.updated_at.clone(),
             ),
@@ -164,33 +136,295 @@ impl UserManager for MySQLPersistence {
         Ok(conn.last_insert_id() as u32)
     }
 
-    fn get_user_by_email(&self, email: &str) -> Result<Option<model::User>, Box<dyn Error>> {
+    fn update_password(&self, id: u32, password: &str, salt: &str) -> Result<(), DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        conn.exec_drop(
+            "UPDATE users SET password = ?, salt = ?, updated_at = ? WHERE id = ?",
+            (password, salt, Utc::now().naive_utc(), id),
+        )?;
+        Ok(())
+    }
+
+    fn get_user(&self, id: u32) -> Result<Option<model::User>, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        let users: Vec<UserRow> = conn.exec(
+            format!("SELECT {USER_COLUMNS} FROM users WHERE id = ?"),
+            (id,),
+        )?;
+        users.into_iter().next().map(user_from_row).transpose()
+    }
+
+    fn get_user_by_email(&self, email: &str) -> Result<Option<model::User>, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        let users: Vec<UserRow> = conn.exec(
+            format!("SELECT {USER_COLUMNS} FROM users WHERE email = ?"),
+            (email,),
+        )?;
+        users.into_iter().next().map(user_from_row).transpose()
+    }
+
+    fn get_users(&self, offset: u32) -> Result<Vec<model::User>, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        let users: Vec<UserRow> = conn.exec(
+            format!("SELECT {USER_COLUMNS} FROM users ORDER BY id LIMIT ?, ?"),
+            (offset, self.page_size),
+        )?;
+        users.into_iter().map(user_from_row).collect()
+    }
+
+    fn update_role(&self, id: u32, role: &model::UserPermission) -> Result<bool, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        conn.exec_drop(
+            "UPDATE users SET role = ?, updated_at = ? WHERE id = ?",
+            (role.as_str(), Utc::now().naive_utc(), id),
+        )?;
+        Ok(rows_matched(&conn) > 0)
+    }
+}
+
+impl ReviewManager for MySQLPersistence {
+    fn create_review(&self, r: &model::Review) -> Result<String, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        conn.exec_drop(
+            "INSERT INTO reviews (book_id, user_id, author, title, content, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            (
+                r.book_id,
+                r.user_id,
+                r.author.clone(),
+                r.title.clone(),
+                r.content.clone(),
+                r.created_at.naive_utc(),
+                r.updated_at.naive_utc(),
+            ),
+        )?;
+        Ok(conn.last_insert_id().to_string())
+    }
+
+    fn update_review(&self, id: &str, r: &model::Review) -> Result<bool, DomainError> {
+        let id = parse_review_id(id)?;
+        let mut conn = self.pool.get_conn()?;
+        conn.exec_drop(
+            "UPDATE reviews SET title = ?, content = ?, updated_at = ? WHERE id = ?",
+            (
+                r.title.clone(),
+                r.content.clone(),
+                Utc::now().naive_utc(),
+                id,
+            ),
+        )?;
+        Ok(rows_matched(&conn) > 0)
+    }
+
+    fn patch_review(&self, id: &str, p: &model::ReviewPatch) -> Result<bool, DomainError> {
+        let id = parse_review_id(id)?;
+        let columns = review_patch_columns(p);
         let mut conn = self.pool.get_conn()?;
-        let users = conn.query_map(
+        conn.exec_drop(
+            format!("UPDATE reviews SET {} WHERE id = ?", set_clause(&columns)),
+            patch_params(columns, Utc::now().naive_utc(), id),
+        )?;
+        Ok(rows_matched(&conn) > 0)
+    }
+
+    fn delete_review(&self, id: &str) -> Result<bool, DomainError> {
+        let id = parse_review_id(id)?;
+        let mut conn = self.pool.get_conn()?;
+        conn.exec_drop("DELETE FROM reviews WHERE id = ?", (id,))?;
+        Ok(conn.affected_rows() > 0)
+    }
+
+    fn get_review(&self, id: &str) -> Result<Option<model::Review>, DomainError> {
+        let id = parse_review_id(id)?;
+        let mut conn = self.pool.get_conn()?;
+        let reviews = conn.exec_map(
+            format!("SELECT {REVIEW_COLUMNS} FROM reviews WHERE id = ?"),
+            (id,),
+            review_from_row,
+        )?;
+        Ok(reviews.into_iter().next())
+    }
+
+    fn get_reviews_of_book(
+        &self,
+        book_id: u32,
+        keyword: &str,
+    ) -> Result<Vec<model::Review>, DomainError> {
+        let mut conn = self.pool.get_conn()?;
+        let term = like_pattern(keyword);
+        let reviews = conn.exec_map(
             format!(
-                "SELECT * FROM users WHERE email = '{}'",
-                email.replace('\'', "")
+                "SELECT {REVIEW_COLUMNS}
+                 FROM reviews WHERE book_id = ? AND (? = ''
+                 OR title LIKE ? ESCAPE '{LIKE_ESCAPE}' OR content LIKE ? ESCAPE '{LIKE_ESCAPE}')"
             ),
-            |(id, email, password, salt, is_admin, created_at, updated_at): (
-                u64,
-                String,
-                String,
-                String,
-                bool,
-                String,
-                String,
-            )| {
-                model::User {
-                    id: id as u32,
-                    email,
-                    password,
-                    salt,
-                    is_admin,
-                    created_at,
-                    updated_at,
-                }
-            },
-        )?;
-        Ok(users.first().cloned())
+            (book_id, keyword, term.clone(), term),
+            review_from_row,
+        )?;
+        Ok(reviews)
+    }
+}
+
+// rows_matched reads "Rows matched: N" from the last UPDATE, because the
+// affected row count leaves out rows whose values did not change.
+fn rows_matched(conn: &PooledConn) -> u64 {
+    conn.info_str()
+        .strip_prefix("Rows matched: ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or_else(|| conn.affected_rows())
+}
+
+// patch_params binds the patched columns, then `updated_at` and the id.
+fn patch_params(
+    columns: Vec<(&'static str, PatchValue)>,
+    updated_at: impl Into<Value>,
+    id: impl Into<Value>,
+) -> Params {
+    let mut values: Vec<Value> = columns
+        .into_iter()
+        .map(|(_, v)| match v {
+            PatchValue::Text(s) => s.into(),
+            PatchValue::Int(n) => n.into(),
+        })
+        .collect();
+    values.push(updated_at.into());
+    values.push(id.into());
+    Params::Positional(values)
+}
+
+const BOOK_COLUMNS: &str =
+    "id, title, author, published_at, description, isbn, total_pages, created_at, updated_at";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+type BookRow = (
+    u64,
+    String,
+    String,
+    NaiveDate,
+    String,
+    String,
+    u64,
+    NaiveDateTime,
+    NaiveDateTime,
+);
+
+fn book_from_row(
+    (id, title, author, published_at, description, isbn, total_pages, created_at, updated_at): BookRow,
+) -> model::Book {
+    model::Book {
+        id: id as u32,
+        title,
+        author,
+        published_at: published_at.format(DATE_FORMAT).to_string(),
+        description,
+        isbn,
+        total_pages: total_pages as u32,
+        created_at: created_at.format(DATETIME_FORMAT).to_string(),
+        updated_at: updated_at.format(DATETIME_FORMAT).to_string(),
+    }
+}
+
+// Columns read into a UserRow, in order.
+const USER_COLUMNS: &str = "id, email, password, salt, role, created_at, updated_at";
+
+type UserRow = (
+    u64,
+    String,
+    String,
+    String,
+    String,
+    NaiveDateTime,
+    NaiveDateTime,
+);
+
+fn user_from_row(
+    (id, email, password, salt, role, created_at, updated_at): UserRow,
+) -> Result<model::User, DomainError> {
+    Ok(model::User {
+        id: id as u32,
+        email,
+        password,
+        salt,
+        role: role.parse().map_err(DomainError::Backend)?,
+        created_at: created_at.format(DATETIME_FORMAT).to_string(),
+        updated_at: updated_at.format(DATETIME_FORMAT).to_string(),
+    })
+}
+
+// Columns read into a ReviewRow, in order.
+const REVIEW_COLUMNS: &str = "id, book_id, user_id, author, title, content, created_at, updated_at";
+
+type ReviewRow = (
+    u64,
+    u32,
+    u32,
+    String,
+    String,
+    String,
+    NaiveDateTime,
+    NaiveDateTime,
+);
+
+fn review_from_row(
+    (id, book_id, user_id, author, title, content, created_at, updated_at): ReviewRow,
+) -> model::Review {
+    model::Review {
+        id: id.to_string(),
+        book_id,
+        user_id,
+        author,
+        title,
+        content,
+        created_at: created_at.and_utc(),
+        updated_at: updated_at.and_utc(),
+    }
+}
+
+impl Migrator for MySQLPersistence {
+    fn migrations(&self) -> &'static [Migration] {
+        MYSQL_MIGRATIONS
+    }
+
+    fn applied_versions(&self) -> Result<Vec<u32>, Box<dyn Error>> {
+        let mut conn = self.pool.get_conn()?;
+        conn.query_drop(format!(
+            "CREATE TABLE IF NOT EXISTS {HISTORY_TABLE} (
+                version INT PRIMARY KEY,
+                name VARCHAR(255) NOT NULL,
+                applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )"
+        ))?;
+        let versions = conn.query(format!("SELECT version FROM {HISTORY_TABLE}"))?;
+        Ok(versions)
+    }
+
+    fn apply(&self, m: &Migration) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.pool.get_conn()?;
+        // MySQL commits DDL implicitly, so the history row is written last.
+        conn.query_drop(m.up)?;
+        conn.exec_drop(
+            format!("INSERT INTO {HISTORY_TABLE} (version, name) VALUES (?, ?)"),
+            (m.version, m.name),
+        )?;
+        Ok(())
+    }
+
+    fn revert(&self, m: &Migration) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.pool.get_conn()?;
+        conn.query_drop(m.down)?;
+        conn.exec_drop(
+            format!("DELETE FROM {HISTORY_TABLE} WHERE version = ?"),
+            (m.version,),
+        )?;
+        Ok(())
+    }
+}
+
+impl Probe for MySQLPersistence {
+    fn ping(&self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        self.pool.try_get_conn(timeout)?.query_drop("SELECT 1")?;
+        Ok(())
     }
 }
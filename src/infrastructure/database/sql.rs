@@ -0,0 +1,91 @@
+use crate::domain::{model, DomainError};
+
+// Escape character used in every `LIKE ... ESCAPE` clause. It is not a
+// backslash, so the SQL stays valid under NO_BACKSLASH_ESCAPES.
+pub const LIKE_ESCAPE: char = '!';
+
+// like_pattern turns a search keyword into a `%keyword%` pattern that
+// matches the keyword literally, wildcards included.
+pub fn like_pattern(keyword: &str) -> String {
+    let mut pattern = String::with_capacity(keyword.len() + 2);
+    pattern.push('%');
+    for c in keyword.chars() {
+        if c == LIKE_ESCAPE || c == '%' || c == '_' {
+            pattern.push(LIKE_ESCAPE);
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+// parse_review_id turns the string id used by the API into a row id.
+pub fn parse_review_id(id: &str) -> Result<i64, DomainError> {
+    id.parse()
+        .map_err(|_| DomainError::Validation(format!("invalid review id {id}")))
+}
+
+// PatchValue is a column value in a partial UPDATE.
+pub enum PatchValue {
+    Text(String),
+    Int(u32),
+}
+
+// book_patch_columns lists the columns a BookPatch changes, in table order.
+pub fn book_patch_columns(p: &model::BookPatch) -> Vec<(&'static str, PatchValue)> {
+    let text = |v: &Option<String>| v.clone().map(PatchValue::Text);
+    [
+        ("title", text(&p.title)),
+        ("author", text(&p.author)),
+        ("published_at", text(&p.published_at)),
+        ("description", text(&p.description)),
+        ("isbn", text(&p.isbn)),
+        ("total_pages", p.total_pages.map(PatchValue::Int)),
+    ]
+    .into_iter()
+    .filter_map(|(col, v)| v.map(|v| (col, v)))
+    .collect()
+}
+
+// review_patch_columns lists the columns a ReviewPatch changes.
+pub fn review_patch_columns(p: &model::ReviewPatch) -> Vec<(&'static str, PatchValue)> {
+    [("title", &p.title), ("content", &p.content)]
+        .into_iter()
+        .filter_map(|(col, v)| v.clone().map(|v| (col, PatchValue::Text(v))))
+        .collect()
+}
+
+// set_clause renders `col1 = ?, ..., updated_at = ?` for the given columns.
+pub fn set_clause(columns: &[(&'static str, PatchValue)]) -> String {
+    columns
+        .iter()
+        .map(|(col, _)| *col)
+        .chain(["updated_at"])
+        .map(|col| format!("{col} = ?"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_pattern_escapes_wildcards_and_the_escape_character() {
+        assert_eq!(like_pattern("100%"), "%100!%%");
+        assert_eq!(like_pattern("a_b"), "%a!_b%");
+        assert_eq!(like_pattern("wow!"), "%wow!!%");
+    }
+
+    #[test]
+    fn like_pattern_keeps_quotes_and_backslashes_as_they_are() {
+        assert_eq!(like_pattern("O'Reilly"), "%O'Reilly%");
+        assert_eq!(like_pattern(r"C:\dir"), r"%C:\dir%");
+        assert_eq!(like_pattern("' OR 1=1 --"), "%' OR 1=1 --%");
+    }
+
+    #[test]
+    fn like_pattern_of_empty_keyword_matches_everything() {
+        assert_eq!(like_pattern(""), "%%");
+    }
+}
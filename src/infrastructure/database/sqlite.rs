@@ -1,27 +1,56 @@
 use std::error::Error;
-use std::sync::Mutex;
+use std::sync::{Mutex, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
-use rusqlite::{params, Connection, Result as RusqliteResult};
+use rusqlite::types::Type;
+use rusqlite::{
+    ffi, params, params_from_iter, Connection, OptionalExtension, Result as RusqliteResult, Row,
+    ToSql,
+};
 
-use crate::domain::gateway::BookManager;
-use crate::domain::model;
+use crate::domain::gateway::{BookManager, ReviewManager, UserManager};
+use crate::domain::{model, DomainError};
+use crate::infrastructure::database::migration::{
+    Migration, Migrator, HISTORY_TABLE, SQLITE_MIGRATIONS,
+};
+use crate::infrastructure::database::sql::{
+    book_patch_columns, like_pattern, parse_review_id, review_patch_columns, set_clause,
+    PatchValue, LIKE_ESCAPE,
+};
+use crate::infrastructure::health::Probe;
 
 pub struct SQLitePersistence {
     conn: Mutex<Connection>,
+    page_size: u32,
 }
 
 impl SQLitePersistence {
-    pub fn new(file_name: &str) -> RusqliteResult<Self> {
+    pub fn new(file_name: &str, page_size: u32) -> RusqliteResult<Self> {
         let conn = Connection::open(file_name)?;
         Ok(SQLitePersistence {
             conn: Mutex::new(conn),
+            page_size,
         })
     }
 }
 
+impl From<rusqlite::Error> for DomainError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err {
+            rusqlite::Error::SqliteFailure(e, msg)
+                if e.extended_code == ffi::SQLITE_CONSTRAINT_UNIQUE =>
+            {
+                DomainError::Conflict(msg.unwrap_or_else(|| e.to_string()))
+            }
+            err => DomainError::Backend(err.to_string()),
+        }
+    }
+}
+
 impl BookManager for SQLitePersistence {
-    fn create_book(&self, b: &model::Book) -> Result<u32, Box<dyn Error>> {
+    fn create_book(&self, b: &model::Book) -> Result<u32, DomainError> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
             "INSERT INTO books (title, author, published_at, description, isbn, total_pages)
@@ -38,9 +67,9 @@ impl BookManager for SQLitePersistence {
         Ok(conn.last_insert_rowid() as u32)
     }
 
-    fn update_book(&self, id: u32, b: &model::Book) -> Result<(), Box<dyn Error>> {
+    fn update_book(&self, id: u32, b: &model::Book) -> Result<bool, DomainError> {
         let conn = self.conn.lock().unwrap();
-        conn.execute(
+        let n = conn.execute(
             "UPDATE books SET title = ?, author = ?, published_at = ?, description = ?, isbn = ?, total_pages = ?, updated_at = ?
              WHERE id = ?",
             params![
@@ -50,58 +79,57 @@ impl BookManager for SQLitePersistence {
                 b.description,
                 b.isbn,
                 b.total_pages,
-                Utc::now().to_rfc3339(),
+                Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
                 id,
             ],
         )?;
-        Ok(())
+        Ok(n > 0)
     }
 
-    fn delete_book(&self, id: u32) -> Result<(), Box<dyn Error>> {
+    fn patch_book(&self, id: u32, p: &model::BookPatch) -> Result<bool, DomainError> {
+        let columns = book_patch_columns(p);
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM books WHERE id = ?1", params![id])?;
-        Ok(())
+        let n = conn.execute(
+            &format!("UPDATE books SET {} WHERE id = ?", set_clause(&columns)),
+            params_from_iter(patch_params(
+                columns,
+                Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                id,
+            )),
+        )?;
+        Ok(n > 0)
     }
 
-    fn get_book(&self, id: u32) -> Result<Option<model::Book>, Box<dyn Error>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM books WHERE id = ?1")?;
-        let book_iter = stmt.query_map(params![id], |row| {
-            Ok(model::Book {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                author: row.get(2)?,
-                published_at: row.get(3)?,
-                description: row.get(4)?,
-                isbn: row.get(5)?,
-                total_pages: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })?;
+    fn delete_book(&self, id: u32) -> Result<bool, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute("DELETE FROM books WHERE id = ?1", params![id])?;
+        Ok(n > 0)
+    }
 
-        for result in book_iter {
-            return Ok(Some(result?));
-        }
-        Ok(None)
-    }
-
-    fn get_books(&self) -> Result<Vec<model::Book>, Box<dyn Error>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM books")?;
-        let book_iter = stmt.query_map([], |row| {
-            Ok(model::Book {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                author: row.get(2)?,
-                published_at: row.get(3)?,
-                description: row.get(4)?,
-                isbn: row.get(5)?,
-                total_pages: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })?;
+    fn get_book(&self, id: u32) -> Result<Option<model::Book>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let book = conn
+            .query_row(
+                "SELECT * FROM books WHERE id = ?1",
+                params![id],
+                book_from_row,
+            )
+            .optional()?;
+        Ok(book)
+    }
+
+    fn get_books(&self, offset: u32, keyword: &str) -> Result<Vec<model::Book>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM books
+             WHERE ?1 = '' OR title LIKE ?2 ESCAPE '{LIKE_ESCAPE}' OR author LIKE ?2 ESCAPE '{LIKE_ESCAPE}'
+             LIMIT ?3 OFFSET ?4"
+        ))?;
+        let term = like_pattern(keyword);
+        let book_iter = stmt.query_map(
+            params![keyword, term, self.page_size, offset],
+            book_from_row,
+        )?;
 
         let mut books = Vec::new();
         for result in book_iter {
@@ -110,3 +138,399 @@ impl BookManager for SQLitePersistence {
         Ok(books)
     }
 }
+
+impl UserManager for SQLitePersistence {
+    fn create_user(&self, u: &model::User) -> Result<u32, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO users (email, password, salt, role, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                u.email,
+                u.password,
+                u.salt,
+                u.role.as_str(),
+                u.created_at,
+                u.updated_at,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid() as u32)
+    }
+
+    fn update_password(&self, id: u32, password: &str, salt: &str) -> Result<(), DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET password = ?, salt = ?, updated_at = ? WHERE id = ?",
+            params![
+                password,
+                salt,
+                Utc::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_user(&self, id: u32) -> Result<Option<model::User>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let user = conn
+            .query_row(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE id = ?1"),
+                params![id],
+                user_from_row,
+            )
+            .optional()?;
+        Ok(user)
+    }
+
+    fn get_user_by_email(&self, email: &str) -> Result<Option<model::User>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let user = conn
+            .query_row(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE email = ?1"),
+                params![email],
+                user_from_row,
+            )
+            .optional()?;
+        Ok(user)
+    }
+
+    fn get_users(&self, offset: u32) -> Result<Vec<model::User>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {USER_COLUMNS} FROM users ORDER BY id LIMIT ?1 OFFSET ?2"
+        ))?;
+        let users = stmt
+            .query_map(params![self.page_size, offset], user_from_row)?
+            .collect::<RusqliteResult<Vec<_>>>()?;
+        Ok(users)
+    }
+
+    fn update_role(&self, id: u32, role: &model::UserPermission) -> Result<bool, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "UPDATE users SET role = ?, updated_at = ? WHERE id = ?",
+            params![
+                role.as_str(),
+                Utc::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                id
+            ],
+        )?;
+        Ok(n > 0)
+    }
+}
+
+impl ReviewManager for SQLitePersistence {
+    fn create_review(&self, r: &model::Review) -> Result<String, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO reviews (book_id, user_id, author, title, content, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                r.book_id,
+                r.user_id,
+                r.author,
+                r.title,
+                r.content,
+                r.created_at,
+                r.updated_at,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid().to_string())
+    }
+
+    fn update_review(&self, id: &str, r: &model::Review) -> Result<bool, DomainError> {
+        let id = parse_review_id(id)?;
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "UPDATE reviews SET title = ?, content = ?, updated_at = ? WHERE id = ?",
+            params![r.title, r.content, Utc::now(), id],
+        )?;
+        Ok(n > 0)
+    }
+
+    fn patch_review(&self, id: &str, p: &model::ReviewPatch) -> Result<bool, DomainError> {
+        let id = parse_review_id(id)?;
+        let columns = review_patch_columns(p);
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            &format!("UPDATE reviews SET {} WHERE id = ?", set_clause(&columns)),
+            params_from_iter(patch_params(columns, Utc::now(), id)),
+        )?;
+        Ok(n > 0)
+    }
+
+    fn delete_review(&self, id: &str) -> Result<bool, DomainError> {
+        let id = parse_review_id(id)?;
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute("DELETE FROM reviews WHERE id = ?1", params![id])?;
+        Ok(n > 0)
+    }
+
+    fn get_review(&self, id: &str) -> Result<Option<model::Review>, DomainError> {
+        let id = parse_review_id(id)?;
+        let conn = self.conn.lock().unwrap();
+        let review = conn
+            .query_row(
+                &format!("SELECT {REVIEW_COLUMNS} FROM reviews WHERE id = ?1"),
+                params![id],
+                review_from_row,
+            )
+            .optional()?;
+        Ok(review)
+    }
+
+    fn get_reviews_of_book(
+        &self,
+        book_id: u32,
+        keyword: &str,
+    ) -> Result<Vec<model::Review>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {REVIEW_COLUMNS} FROM reviews
+             WHERE book_id = ?1 AND (?2 = ''
+             OR title LIKE ?3 ESCAPE '{LIKE_ESCAPE}' OR content LIKE ?3 ESCAPE '{LIKE_ESCAPE}')"
+        ))?;
+        let term = like_pattern(keyword);
+        let review_iter = stmt.query_map(params![book_id, keyword, term], review_from_row)?;
+
+        let mut reviews = Vec::new();
+        for result in review_iter {
+            reviews.push(result?);
+        }
+        Ok(reviews)
+    }
+}
+impl Migrator for SQLitePersistence {
+    fn migrations(&self) -> &'static [Migration] {
+        SQLITE_MIGRATIONS
+    }
+
+    fn applied_versions(&self) -> Result<Vec<u32>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {HISTORY_TABLE} (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )"
+        ))?;
+        let mut stmt = conn.prepare(&format!("SELECT version FROM {HISTORY_TABLE}"))?;
+        let versions = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<RusqliteResult<Vec<u32>>>()?;
+        Ok(versions)
+    }
+
+    fn apply(&self, m: &Migration) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute_batch(m.up)?;
+        tx.execute(
+            &format!("INSERT INTO {HISTORY_TABLE} (version, name) VALUES (?1, ?2)"),
+            params![m.version, m.name],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn revert(&self, m: &Migration) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute_batch(m.down)?;
+        tx.execute(
+            &format!("DELETE FROM {HISTORY_TABLE} WHERE version = ?1"),
+            params![m.version],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+// patch_params binds the patched columns, then `updated_at` and the id.
+fn patch_params(
+    columns: Vec<(&'static str, PatchValue)>,
+    updated_at: impl ToSql + 'static,
+    id: impl ToSql + 'static,
+) -> Vec<Box<dyn ToSql>> {
+    let mut values: Vec<Box<dyn ToSql>> = columns
+        .into_iter()
+        .map(|(_, v)| -> Box<dyn ToSql> {
+            match v {
+                PatchValue::Text(s) => Box::new(s),
+                PatchValue::Int(n) => Box::new(n),
+            }
+        })
+        .collect();
+    values.push(Box::new(updated_at));
+    values.push(Box::new(id));
+    values
+}
+
+fn book_from_row(row: &Row) -> RusqliteResult<model::Book> {
+    Ok(model::Book {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        author: row.get(2)?,
+        published_at: row.get(3)?,
+        description: row.get(4)?,
+        isbn: row.get(5)?,
+        total_pages: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+// Columns read by user_from_row, in order.
+const USER_COLUMNS: &str = "id, email, password, salt, role, created_at, updated_at";
+
+fn user_from_row(row: &Row) -> RusqliteResult<model::User> {
+    let role: String = row.get(4)?;
+    Ok(model::User {
+        id: row.get(0)?,
+        email: row.get(1)?,
+        password: row.get(2)?,
+        salt: row.get(3)?,
+        role: role.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(4, Type::Text, e.into())
+        })?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+// Columns read by review_from_row, in order.
+const REVIEW_COLUMNS: &str = "id, book_id, user_id, author, title, content, created_at, updated_at";
+
+fn review_from_row(row: &Row) -> RusqliteResult<model::Review> {
+    Ok(model::Review {
+        id: row.get::<_, i64>(0)?.to_string(),
+        book_id: row.get(1)?,
+        user_id: row.get(2)?,
+        author: row.get(3)?,
+        title: row.get(4)?,
+        content: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+// How often a ping retries taking the connection.
+const PING_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+impl Probe for SQLitePersistence {
+    // ping polls for the connection, which a slow query may hold for as long
+    // as SQLite's busy timeout.
+    fn ping(&self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        let conn = loop {
+            match self.conn.try_lock() {
+                Ok(conn) => break conn,
+                Err(TryLockError::Poisoned(_)) => return Err("connection is poisoned".into()),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(PING_POLL_INTERVAL)
+                }
+                Err(TryLockError::WouldBlock) => return Err("connection is busy".into()),
+            }
+        };
+        conn.query_row("SELECT 1", [], |_| Ok(()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::infrastructure::database::migration::migrate_up;
+
+    const TITLES: [&str; 5] = [
+        "100% Rust",
+        "1000 Recipes",
+        "snake_case style",
+        "snakeXcase style",
+        "It's ' OR 1=1 -- a trap",
+    ];
+
+    fn library() -> SQLitePersistence {
+        let p = SQLitePersistence::new(":memory:", 50).unwrap();
+        migrate_up(&p).unwrap();
+        for title in TITLES {
+            p.create_book(&model::Book {
+                id: 0,
+                title: title.to_string(),
+                author: "Someone".to_string(),
+                published_at: "2020-01-01".to_string(),
+                description: String::new(),
+                isbn: String::new(),
+                total_pages: 1,
+                created_at: String::new(),
+                updated_at: String::new(),
+            })
+            .unwrap();
+            p.create_review(&model::Review {
+                id: String::new(),
+                book_id: 1,
+                user_id: 1,
+                author: "someone".to_string(),
+                title: "review".to_string(),
+                content: title.to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .unwrap();
+        }
+        p
+    }
+
+    fn book_titles(p: &SQLitePersistence, keyword: &str) -> Vec<String> {
+        let books = p.get_books(0, keyword).unwrap();
+        books.into_iter().map(|b| b.title).collect()
+    }
+
+    fn review_contents(p: &SQLitePersistence, keyword: &str) -> Vec<String> {
+        let reviews = p.get_reviews_of_book(1, keyword).unwrap();
+        reviews.into_iter().map(|r| r.content).collect()
+    }
+
+    #[test]
+    fn percent_matches_only_a_literal_percent() {
+        let p = library();
+        assert_eq!(book_titles(&p, "%"), ["100% Rust"]);
+        assert_eq!(book_titles(&p, "100%"), ["100% Rust"]);
+        assert_eq!(review_contents(&p, "%"), ["100% Rust"]);
+    }
+
+    #[test]
+    fn underscore_matches_only_a_literal_underscore() {
+        let p = library();
+        assert_eq!(book_titles(&p, "_"), ["snake_case style"]);
+        assert_eq!(book_titles(&p, "snake_case"), ["snake_case style"]);
+        assert_eq!(review_contents(&p, "_"), ["snake_case style"]);
+    }
+
+    #[test]
+    fn escape_character_matches_only_itself() {
+        let p = library();
+        assert!(book_titles(&p, "!").is_empty());
+        assert!(book_titles(&p, "!%").is_empty());
+    }
+
+    #[test]
+    fn injection_attempt_is_searched_as_text() {
+        let p = library();
+        assert_eq!(book_titles(&p, "' OR 1=1 --"), ["It's ' OR 1=1 -- a trap"]);
+        assert_eq!(
+            review_contents(&p, "' OR 1=1 --"),
+            ["It's ' OR 1=1 -- a trap"]
+        );
+        assert!(book_titles(&p, "x' OR '1'='1").is_empty());
+    }
+
+    #[test]
+    fn empty_keyword_lists_everything() {
+        let p = library();
+        assert_eq!(book_titles(&p, "").len(), TITLES.len());
+    }
+}
@@ -0,0 +1,19 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Probe is implemented by every backend that can become unreachable.
+pub trait Probe: Send + Sync {
+    // ping makes one cheap round trip to the backend, waiting at most about
+    // `timeout` for a connection to it.
+    fn ping(&self, timeout: Duration) -> Result<(), Box<dyn Error>>;
+}
+
+// Dependency is a backend the service was configured with. A required one
+// being down makes the service unable to answer requests.
+#[derive(Clone)]
+pub struct Dependency {
+    pub name: &'static str,
+    pub required: bool,
+    pub probe: Arc<dyn Probe>,
+}
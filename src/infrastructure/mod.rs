@@ -1,5 +1,7 @@
 mod config;
-pub use config::{parse_config, Config};
+pub use config::{parse_config, CacheBackend, Config, DatabaseBackend, ReviewBackend};
 pub mod cache;
 pub mod database;
+pub mod health;
+pub mod password;
 pub mod token;
@@ -0,0 +1,79 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha1::{Digest, Sha1};
+use subtle::ConstantTimeEq;
+
+use crate::domain::gateway::{PasswordCheck, PasswordManager};
+use crate::domain::DomainError;
+
+// Hasher stores passwords as Argon2id PHC strings. It still accepts the
+// legacy `sha1(password + salt)` hex digests so they can be upgraded when
+// their owners sign in.
+pub struct Hasher {
+    argon2: Argon2<'static>,
+}
+
+impl Hasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, argon2::Error> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)?;
+        Ok(Hasher {
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+        })
+    }
+
+    // is_current tells whether `hash` was made with today's algorithm and cost.
+    fn is_current(&self, hash: &PasswordHash) -> bool {
+        let current = self.argon2.params();
+        hash.algorithm == Algorithm::Argon2id.ident()
+            && hash.version == Some(Version::V0x13.into())
+            && Params::try_from(hash).is_ok_and(|p| {
+                p.m_cost() == current.m_cost()
+                    && p.t_cost() == current.t_cost()
+                    && p.p_cost() == current.p_cost()
+            })
+    }
+}
+
+impl PasswordManager for Hasher {
+    fn hash_password(&self, password: &str) -> Result<String, DomainError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self
+            .argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| DomainError::Backend(format!("failed to hash password: {e}")))?;
+        Ok(hash.to_string())
+    }
+
+    fn verify_password(
+        &self,
+        password: &str,
+        hash: &str,
+        salt: &str,
+    ) -> Result<PasswordCheck, DomainError> {
+        // PHC strings start with `$`; anything else is a legacy hex digest.
+        if !hash.starts_with('$') {
+            let digest = hex::encode(Sha1::digest(format!("{password}{salt}")));
+            return Ok(if bool::from(digest.as_bytes().ct_eq(hash.as_bytes())) {
+                PasswordCheck::MatchNeedsRehash
+            } else {
+                PasswordCheck::Mismatch
+            });
+        }
+        let parsed = PasswordHash::new(hash)
+            .map_err(|e| DomainError::Backend(format!("malformed password hash: {e}")))?;
+        // The output comparison inside verify_password is constant time.
+        if self
+            .argon2
+            .verify_password(password.as_bytes(), &parsed)
+            .is_err()
+        {
+            return Ok(PasswordCheck::Mismatch);
+        }
+        Ok(if self.is_current(&parsed) {
+            PasswordCheck::Match
+        } else {
+            PasswordCheck::MatchNeedsRehash
+        })
+    }
+}
@@ -0,0 +1,3 @@
+mod argon;
+
+pub use argon::Hasher;
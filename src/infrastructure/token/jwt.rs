@@ -1,16 +1,39 @@
-use std::error::Error;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
 use crate::domain::gateway::PermissionManager;
-use crate::domain::model;
+use crate::domain::{model, DomainError};
+use crate::infrastructure::cache;
 
 // Keeper manages user tokens.
+//
+// A sign-in starts a refresh chain: an access token plus a refresh token
+// sharing a chain id. Each refresh token can be exchanged once for the next
+// pair of the chain. Revoked token ids and chains are kept in the cache
+// until the tokens they cover have expired anyway.
+//
+// Cache errors fail the call: a token is not taken as unrevoked, nor a
+// sign-out as done, unless the cache answered. With `fail_open` access tokens
+// are accepted unchecked while revocations cannot be read.
 pub struct Keeper {
     secret_key: String,
-    expire_hours: u64,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+    revocations: Arc<dyn cache::Helper>,
+    key_prefix: String,
+    fail_open: bool,
+}
+
+// TokenKind tells access tokens from refresh tokens, so that neither can
+// stand in for the other.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TokenKind {
+    Access,
+    Refresh,
 }
 
 // UserClaims includes user info.
@@ -19,65 +42,298 @@ pub struct UserClaims {
     user_id: u32,
     user_name: String,
     permission: model::UserPermission,
-    exp: usize, // Expiry time in seconds since epoch
+    typ: TokenKind,
+    jti: String,   // Unique token id
+    chain: String, // Id shared by the tokens of one sign-in
+    exp: usize,    // Expiry time in seconds since epoch
 }
 
 impl Keeper {
     // NewTokenKeeper constructs a new JWT token keeper
-    pub fn new(secret_key: String, expire_in_hours: u32) -> Self {
+    pub fn new(
+        secret_key: String,
+        access_minutes: u32,
+        refresh_hours: u32,
+        revocations: Arc<dyn cache::Helper>,
+        key_prefix: &str,
+        fail_open: bool,
+    ) -> Self {
         Keeper {
             secret_key,
-            expire_hours: expire_in_hours as u64,
+            access_ttl: Duration::from_secs(access_minutes as u64 * 60),
+            refresh_ttl: Duration::from_secs(refresh_hours as u64 * 3600),
+            revocations,
+            key_prefix: format!("{key_prefix}auth-"),
+            fail_open,
         }
     }
 
-    // extract_token extracts the token from the signed string.
-    fn extract_token(&self, token_result: &str) -> Result<UserClaims, Box<dyn Error>> {
+    // extract_token extracts the token of the given kind from the signed
+    // string, rejecting it if it has been revoked.
+    fn extract_token(
+        &self,
+        token_result: &str,
+        kind: TokenKind,
+    ) -> Result<UserClaims, DomainError> {
         let token_data = decode::<UserClaims>(
             token_result,
             &DecodingKey::from_secret(self.secret_key.as_ref()),
             &Validation::default(),
-        )?;
-        Ok(token_data.claims)
+        )
+        .map_err(|e| DomainError::Unauthorized(format!("invalid token: {e}")))?;
+        let claims = token_data.claims;
+        if claims.typ != kind {
+            return Err(DomainError::Unauthorized(
+                "invalid token: wrong token type".to_string(),
+            ));
+        }
+        if self.is_revoked(&self.token_key(&claims.jti))?
+            || self.is_revoked(&self.chain_key(&claims.chain))?
+        {
+            return Err(DomainError::Unauthorized(
+                "token has been revoked".to_string(),
+            ));
+        }
+        Ok(claims)
+    }
+
+    // issue signs a new token pair in `chain`.
+    fn issue(
+        &self,
+        user_id: u32,
+        email: &str,
+        perm: model::UserPermission,
+        chain: String,
+    ) -> Result<model::TokenPair, DomainError> {
+        let sign = |kind: TokenKind, perm: model::UserPermission, ttl: Duration| {
+            let claims = UserClaims {
+                user_id,
+                user_name: email.to_owned(),
+                permission: perm,
+                typ: kind,
+                jti: random_id(),
+                chain: chain.clone(),
+                exp: expire_at(ttl)?,
+            };
+            encode(
+                &Header::default(),
+                &claims,
+                &EncodingKey::from_secret(self.secret_key.as_ref()),
+            )
+            .map_err(|e| DomainError::Backend(e.to_string()))
+        };
+        Ok(model::TokenPair {
+            access_token: sign(TokenKind::Access, perm.clone(), self.access_ttl)?,
+            refresh_token: sign(TokenKind::Refresh, perm, self.refresh_ttl)?,
+            expires_in: self.access_ttl.as_secs(),
+        })
+    }
+
+    // is_revoked reads a revocation marker.
+    fn is_revoked(&self, key: &str) -> Result<bool, DomainError> {
+        match self.revocations.load(key) {
+            Ok(v) => Ok(v.is_some()),
+            Err(e) if self.fail_open => {
+                warn!("failed to check token revocation {:?}: {}", key, e);
+                Ok(false)
+            }
+            Err(e) => Err(DomainError::Backend(format!(
+                "failed to check token revocation: {e}"
+            ))),
+        }
+    }
+
+    fn revoke(&self, key: &str, ttl: Duration) -> Result<(), DomainError> {
+        self.revocations
+            .save(key, "1", ttl.max(Duration::from_secs(1)))
+            .map_err(|e| DomainError::Backend(format!("failed to revoke token: {e}")))
+    }
+
+    // revoke_chain revokes every token sharing `chain`. None of them
+    // outlives a refresh token issued now.
+    fn revoke_chain(&self, chain: &str) -> Result<(), DomainError> {
+        self.revoke(&self.chain_key(chain), self.refresh_ttl)
+    }
+
+    fn token_key(&self, jti: &str) -> String {
+        format!("{}revoked-{}", self.key_prefix, jti)
+    }
+
+    fn chain_key(&self, chain: &str) -> String {
+        format!("{}revoked-chain-{}", self.key_prefix, chain)
+    }
+
+    fn used_key(&self, jti: &str) -> String {
+        format!("{}used-{}", self.key_prefix, jti)
     }
 }
 
 impl PermissionManager for Keeper {
-    // generate_token generates a new JWT token.
-    fn generate_token(
+    // generate_tokens generates a new pair of JWT tokens.
+    fn generate_tokens(
         &self,
         user_id: u32,
         email: &str,
         perm: model::UserPermission,
-    ) -> Result<String, Box<dyn Error>> {
-        let exp = SystemTime::now()
-            .checked_add(Duration::from_secs(self.expire_hours * 3600))
-            .ok_or("Overflow when adding expire time")?;
-        let exp = exp.duration_since(SystemTime::UNIX_EPOCH)?.as_secs() as usize;
-
-        let claims = UserClaims {
-            user_id,
-            user_name: email.to_owned(),
-            permission: perm,
-            exp,
-        };
+    ) -> Result<model::TokenPair, DomainError> {
+        self.issue(user_id, email, perm, random_id())
+    }
 
-        let header = Header::default();
-        let token = encode(
-            &header,
-            &claims,
-            &EncodingKey::from_secret(self.secret_key.as_ref()),
-        )?;
-        Ok(token)
+    // redeem_refresh_token rotates a refresh token. Presenting one that was
+    // already rotated means it leaked, so its whole chain is revoked.
+    fn redeem_refresh_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<model::RefreshGrant, DomainError> {
+        let claims = self.extract_token(refresh_token, TokenKind::Refresh)?;
+        let first_use = self
+            .revocations
+            .add(&self.used_key(&claims.jti), "1", time_left(claims.exp))
+            .map_err(|e| DomainError::Backend(format!("failed to rotate token: {e}")))?;
+        if !first_use {
+            warn!(
+                "refresh token of user {} was reused, revoking its chain",
+                claims.user_id
+            );
+            self.revoke_chain(&claims.chain)?;
+            return Err(DomainError::Unauthorized(
+                "refresh token has already been used".to_string(),
+            ));
+        }
+        Ok(model::RefreshGrant {
+            user_id: claims.user_id,
+            chain: claims.chain,
+        })
     }
 
-    // has_permission checks if user has the given permission.
-    fn has_permission(
+    fn continue_chain(
         &self,
-        token_result: &str,
+        grant: &model::RefreshGrant,
+        email: &str,
         perm: model::UserPermission,
-    ) -> Result<bool, Box<dyn Error>> {
-        let claims = self.extract_token(token_result)?;
-        Ok(claims.permission >= perm)
+    ) -> Result<model::TokenPair, DomainError> {
+        self.issue(grant.user_id, email, perm, grant.chain.clone())
+    }
+
+    // revoke_tokens signs a user out.
+    fn revoke_tokens(&self, access_token: &str) -> Result<(), DomainError> {
+        let claims = self.extract_token(access_token, TokenKind::Access)?;
+        self.revoke(&self.token_key(&claims.jti), time_left(claims.exp))?;
+        self.revoke_chain(&claims.chain)
+    }
+
+    fn authenticate(&self, access_token: &str) -> Result<model::AuthUser, DomainError> {
+        let claims = self.extract_token(access_token, TokenKind::Access)?;
+        Ok(model::AuthUser {
+            id: claims.user_id,
+            email: claims.user_name,
+            permission: claims.permission,
+        })
+    }
+}
+
+fn random_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+fn expire_at(ttl: Duration) -> Result<usize, DomainError> {
+    let exp = SystemTime::now()
+        .checked_add(ttl)
+        .ok_or_else(|| DomainError::Backend("Overflow when adding expire time".to_string()))?;
+    Ok(exp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|e| DomainError::Backend(e.to_string()))?
+        .as_secs() as usize)
+}
+
+// time_left is how long a token expiring at `exp` is still accepted,
+// including the validator's leeway.
+fn time_left(exp: usize) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let leeway = Validation::default().leeway;
+    Duration::from_secs((exp as u64 + leeway).saturating_sub(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    // Down is a cache that cannot be reached.
+    struct Down;
+
+    impl cache::Helper for Down {
+        fn save(&self, _: &str, _: &str, _: Duration) -> Result<(), Box<dyn Error>> {
+            Err("down".into())
+        }
+        fn load(&self, _: &str) -> Result<Option<String>, Box<dyn Error>> {
+            Err("down".into())
+        }
+        fn add(&self, _: &str, _: &str, _: Duration) -> Result<bool, Box<dyn Error>> {
+            Err("down".into())
+        }
+        fn incr(&self, _: &str) -> Result<i64, Box<dyn Error>> {
+            Err("down".into())
+        }
+        fn flush(&self, _: &str, _: &[&str]) -> Result<u64, Box<dyn Error>> {
+            Err("down".into())
+        }
+        fn stats(&self) -> Vec<cache::TierStats> {
+            Vec::new()
+        }
+    }
+
+    fn keeper(revocations: Arc<dyn cache::Helper>, fail_open: bool) -> Keeper {
+        Keeper::new(
+            "secret".to_string(),
+            15,
+            48,
+            revocations,
+            "test-",
+            fail_open,
+        )
+    }
+
+    fn access_token(k: &Keeper) -> String {
+        k.generate_tokens(1, "a@b.c", model::UserPermission::User)
+            .unwrap()
+            .access_token
+    }
+
+    #[test]
+    fn signed_out_token_is_rejected() {
+        let k = keeper(Arc::new(cache::InMemoryCache::new()), false);
+        let token = access_token(&k);
+        assert!(k.authenticate(&token).is_ok());
+        k.revoke_tokens(&token).unwrap();
+        assert!(matches!(
+            k.authenticate(&token),
+            Err(DomainError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn unreachable_cache_fails_authentication() {
+        let k = keeper(Arc::new(Down), false);
+        let token = access_token(&k);
+        assert!(matches!(
+            k.authenticate(&token),
+            Err(DomainError::Backend(_))
+        ));
+    }
+
+    #[test]
+    fn unreachable_cache_fails_sign_out_even_when_failing_open() {
+        let k = keeper(Arc::new(Down), true);
+        let token = access_token(&k);
+        assert!(k.authenticate(&token).is_ok());
+        assert!(matches!(
+            k.revoke_tokens(&token),
+            Err(DomainError::Backend(_))
+        ));
     }
 }
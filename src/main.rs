@@ -6,16 +6,71 @@ mod application;
 mod domain;
 mod infrastructure;
 
+use rocket::fairing::AdHoc;
+
 use crate::adapter::router::*;
-use crate::infrastructure::parse_config;
+use crate::infrastructure::{parse_config, Config};
+
+// Names the config file when `--config` is not given.
+const CONFIG_ENV: &str = "LRBOOKS_CONFIG";
 
-const CONFIG_FILE: &str = "config.toml";
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let config_file = match take_config_flag(&mut args) {
+        Ok(f) => f.or_else(|| std::env::var(CONFIG_ENV).ok()),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let c = match parse_config(config_file.as_deref()) {
+        Ok(c) => c,
+        Err(err) => {
+            eprintln!("Invalid configuration: {err}");
+            std::process::exit(1);
+        }
+    };
+    match args.first().map(String::as_str) {
+        None => {
+            if let Err(err) = rocket::execute(rocket(c).launch()) {
+                err.pretty_print();
+                std::process::exit(1);
+            }
+        }
+        Some("migrate") => {
+            if let Err(err) = adapter::cli::migrate(&c, &args[1..]) {
+                eprintln!("Migration failed: {err}");
+                std::process::exit(1);
+            }
+        }
+        Some(cmd) => {
+            eprintln!("Unknown command: {cmd}");
+            std::process::exit(2);
+        }
+    }
+}
+
+// take_config_flag removes `--config <path>` or `--config=<path>` from args.
+fn take_config_flag(args: &mut Vec<String>) -> Result<Option<String>, String> {
+    let Some(i) = args
+        .iter()
+        .position(|a| a == "--config" || a.starts_with("--config="))
+    else {
+        return Ok(None);
+    };
+    let flag = args.remove(i);
+    match flag.strip_prefix("--config=") {
+        Some(path) => Ok(Some(path.to_string())),
+        None if i < args.len() => Ok(Some(args.remove(i))),
+        None => Err("--config needs a file path".to_string()),
+    }
+}
 
-#[launch]
-fn rocket() -> _ {
-    let c = parse_config(CONFIG_FILE);
+fn rocket(c: Config) -> rocket::Rocket<rocket::Build> {
     let wire_helper = application::WireHelper::new(&c).expect("Failed to create WireHelper");
     let r = adapter::make_router(&wire_helper);
+    let cache_helper = wire_helper.cache_helper();
+    let (warm_up_pages, page_size) = (c.cache.warm_up_pages, c.app.page_size);
     rocket::build()
         .configure(
             rocket::Config::figment()
@@ -23,22 +78,56 @@ fn rocket() -> _ {
                 .merge(("address", c.app.address)),
         )
         .manage(r)
+        .register("/", catchers![default_catcher])
+        .attach(AdHoc::on_ignite("Cache warm-up", move |rocket| {
+            Box::pin(async move {
+                if warm_up_pages > 0 {
+                    let r = rocket.state::<RestHandler>().unwrap();
+                    match blocking(|| r.warm_up_cache(warm_up_pages, page_size)) {
+                        Ok(n) => info!("warmed up {} book list pages", n),
+                        Err(err) => warn!("cache warm-up failed: {}", err),
+                    }
+                }
+                rocket
+            })
+        }))
+        .attach(AdHoc::on_shutdown("Cache stats", |_| {
+            Box::pin(async move {
+                for s in cache_helper.stats() {
+                    info!(
+                        "cache tier {}: {} hits, {} misses",
+                        s.tier, s.hits, s.misses
+                    );
+                }
+            })
+        }))
         .mount(
             "/",
             routes![
                 health_check,
+                liveness,
+                readiness,
                 get_books,
                 get_book,
                 create_book,
                 update_book,
+                patch_book,
                 delete_book,
                 get_reviews_of_book,
                 get_review,
                 create_review,
                 update_review,
+                patch_review,
                 delete_review,
                 user_sign_up,
-                user_sign_in
+                user_sign_in,
+                user_refresh,
+                user_sign_out,
+                get_me,
+                get_users,
+                update_user_role,
+                flush_cache,
+                get_cache_stats
             ],
         )
 }